@@ -7,3 +7,11 @@ pub const MINIMUM_API_VERSION: &str = "0.1.1";
 pub const LIBRARY_PROVIDER_NAME: &str = "Local games";
 pub const LIBRARY_PROVIDER_ID: &str = "local";
 pub const LIBRARY_SUBDIR: &str = "playtron/apps/local";
+pub const PLUGIN_DATA_SUBDIR: &str = "playtron/plugins/local/data";
+pub const TRASH_DIR: &str = ".trash";
+pub const TRASH_MAX_AGE_DAYS: i64 = 30;
+pub const TRASH_MIN_FREE_SPACE_PERCENT: u64 = 10;
+pub const SAVE_SNAPSHOT_RETENTION: usize = 20;
+pub const CLOUD_SYNC_CONFIG_FILE: &str = "cloud_sync.yaml";
+pub const SAVE_MANIFEST_FILE: &str = "manifest.yaml";
+pub const LAUNCH_SCRIPT_TIMEOUT_SECS: u64 = 60;
@@ -0,0 +1,697 @@
+//! Save sync for local games. The save files declared in gameinfo.yaml are
+//! uploaded to, or downloaded from, a target configured in `cloud_sync.yaml`
+//! inside the plugin data folder. The target is either a folder (e.g. on
+//! another disk or a network share) or a WebDAV server:
+//!
+//! ```yaml
+//! target: folder
+//! path: /run/media/playtron/backup/saves
+//! ```
+//!
+//! ```yaml
+//! target: webdav
+//! url: https://cloud.example.com/remote.php/dav/files/me/playtron
+//! username: me
+//! password: secret
+//! quota: 1073741824 # optional, in bytes
+//! ```
+//!
+//! Remote layout, per app:
+//!
+//! ```text
+//! <app_id>/manifest.json
+//! <app_id>/files/<alias>/<path relative to the save location>
+//! ```
+
+use crate::constants::{CLOUD_SYNC_CONFIG_FILE, PLUGIN_DATA_SUBDIR};
+use crate::local::save_snapshots::list_save_files;
+use crate::local::webdav::WebDavBackend;
+use crate::plugin::errors::PluginError;
+use crate::types::cloud_sync::{CloudFailureReason, CloudPath, CloudSyncOperation};
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::disks::{get_disk_space, get_mount_points};
+use crate::utils::system::get_plugin_data_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SYNC_STATE_SUBDIR: &str = "cloud_sync";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "target", rename_all = "lowercase")]
+pub enum CloudSyncTarget {
+    Folder {
+        path: PathBuf,
+    },
+    Webdav {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CloudSyncConfig {
+    #[serde(flatten)]
+    pub target: CloudSyncTarget,
+    /// Maximum number of bytes to store on the target
+    pub quota: Option<u64>,
+}
+
+/// Storage holding the synced saves
+pub trait SyncBackend {
+    /// Returns the contents of a remote file, or `None` if it doesn't exist
+    fn read(&self, path: &str) -> ResultWithError<Option<Vec<u8>>>;
+    /// Write a remote file, failing with `PluginError::CloudQuota` when out of space
+    fn write(&self, path: &str, data: &[u8]) -> EmptyResult;
+    fn delete(&self, path: &str) -> EmptyResult;
+    /// Returns the used and total space of the target, in bytes, if known
+    fn usage(&self) -> ResultWithError<Option<(u64, u64)>>;
+}
+
+struct FolderBackend {
+    root: PathBuf,
+}
+
+impl SyncBackend for FolderBackend {
+    fn read(&self, path: &str) -> ResultWithError<Option<Vec<u8>>> {
+        match fs::read(self.root.join(path)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> EmptyResult {
+        let path = self.root.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // Hidden and unique, so it can't collide with another save file
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}.{}.partial",
+            path.file_name().unwrap_or_default().to_string_lossy(),
+            std::process::id(),
+            nanos
+        ));
+        match fs::write(&tmp_path, data) {
+            Ok(_) => Ok(fs::rename(&tmp_path, &path)?),
+            Err(e) => {
+                let _ = fs::remove_file(&tmp_path);
+                if e.kind() == io::ErrorKind::StorageFull {
+                    Err(PluginError::CloudQuota.into())
+                } else {
+                    Err(e.into())
+                }
+            }
+        }
+    }
+
+    fn delete(&self, path: &str) -> EmptyResult {
+        match fs::remove_file(self.root.join(path)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn usage(&self) -> ResultWithError<Option<(u64, u64)>> {
+        let used = fs_extra::dir::get_size(&self.root).unwrap_or_default();
+        Ok(get_disk_space(&self.root).map(|(available, _)| (used, used + available)))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct RemoteFile {
+    alias: String,
+    /// Path relative to the save location, with `/` separators
+    path: String,
+    size: u64,
+    /// Modification time, in seconds since epoch
+    modified: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct RemoteManifest {
+    /// Modification time of the newest save file, in seconds since epoch
+    timestamp: u64,
+    files: Vec<RemoteFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SyncState {
+    /// Timestamp of the saves the last time they were in sync with the target
+    last_synced: u64,
+}
+
+struct LocalFile {
+    remote: RemoteFile,
+    local_path: PathBuf,
+}
+
+/// Why a sync failed
+pub enum SyncFailure {
+    /// A failure that can be resolved by the user, such as a conflict
+    Reason(CloudFailureReason),
+    Error(PluginError, String),
+}
+
+impl SyncFailure {
+    /// A failure while transferring files in the direction of `operation`,
+    /// local read and write errors included
+    pub fn transfer(operation: CloudSyncOperation, error: impl std::fmt::Display) -> Self {
+        let plugin_error = match operation {
+            CloudSyncOperation::Upload => PluginError::CloudFileUpload,
+            CloudSyncOperation::Download => PluginError::CloudFileDownload,
+        };
+        SyncFailure::Error(plugin_error, error.to_string())
+    }
+}
+
+pub fn load_config() -> ResultWithError<Option<CloudSyncConfig>> {
+    let config_path = dirs::data_dir()
+        .ok_or("Can't get data dir")?
+        .join(PLUGIN_DATA_SUBDIR)
+        .join(CLOUD_SYNC_CONFIG_FILE);
+    if !config_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_yaml::from_str(&fs::read_to_string(
+        config_path,
+    )?)?))
+}
+
+fn create_backend(config: &CloudSyncConfig) -> ResultWithError<Box<dyn SyncBackend>> {
+    match &config.target {
+        CloudSyncTarget::Folder { path } => {
+            // Don't fill up the mount point folder of a disk that was unplugged
+            let is_on_missing_disk = path.starts_with("/run/media") || path.starts_with("/media");
+            if is_on_missing_disk
+                && !get_mount_points()
+                    .iter()
+                    .any(|mount_point| path.starts_with(mount_point))
+            {
+                return Err(PluginError::DiskNotFound.into());
+            }
+            fs::create_dir_all(path)?;
+            Ok(Box::new(FolderBackend { root: path.clone() }))
+        }
+        CloudSyncTarget::Webdav {
+            url,
+            username,
+            password,
+        } => Ok(Box::new(WebDavBackend::new(
+            url,
+            username.clone(),
+            password.clone(),
+        )?)),
+    }
+}
+
+/// Upload or download the save files of an app.
+/// Fails with a conflict when both sides changed since the last sync, unless
+/// `conflict_resolution` is set, in which case the requested direction wins.
+pub fn sync_saves(
+    app_id: &str,
+    save_paths: &[CloudPath],
+    operation: CloudSyncOperation,
+    conflict_resolution: bool,
+    on_progress: &dyn Fn(f64),
+) -> Result<(), SyncFailure> {
+    let config = load_config()
+        .map_err(|e| SyncFailure::Error(PluginError::DependencyError, e.to_string()))?
+        .ok_or(SyncFailure::Error(
+            PluginError::DependencyError,
+            "Cloud sync isn't configured".to_string(),
+        ))?;
+    let backend = create_backend(&config)
+        .map_err(|e| SyncFailure::Error(PluginError::DiskNotFound, e.to_string()))?;
+
+    let local_files = list_local_files(save_paths).map_err(|e| {
+        SyncFailure::transfer(operation, format!("Can't read the local saves: {}", e))
+    })?;
+    let local_timestamp = local_files
+        .iter()
+        .map(|file| file.remote.modified)
+        .max()
+        .unwrap_or_default();
+    let manifest_path = format!("{}/manifest.json", app_id);
+    let remote_manifest: Option<RemoteManifest> = match backend.read(&manifest_path) {
+        Ok(Some(data)) => Some(serde_json::from_slice(&data).map_err(|e| {
+            SyncFailure::Error(
+                PluginError::CloudFileDownload,
+                format!("Invalid manifest on the target: {}", e),
+            )
+        })?),
+        Ok(None) => None,
+        Err(e) => {
+            return Err(SyncFailure::Error(
+                PluginError::CloudFileDownload,
+                e.to_string(),
+            ))
+        }
+    };
+    let remote_timestamp = remote_manifest
+        .as_ref()
+        .map(|manifest| manifest.timestamp)
+        .unwrap_or_default();
+    let mut state = load_sync_state(app_id);
+
+    log::info!(
+        "Syncing saves of {} ({:?}), local: {}, remote: {}, last synced: {}",
+        app_id,
+        operation,
+        local_timestamp,
+        remote_timestamp,
+        state.last_synced
+    );
+
+    match operation {
+        CloudSyncOperation::Upload => {
+            // Someone else uploaded saves since we last synced
+            let remote_changed = remote_manifest.is_some() && remote_timestamp > state.last_synced;
+            if remote_changed && remote_timestamp != local_timestamp && !conflict_resolution {
+                return Err(SyncFailure::Reason(CloudFailureReason::Conflict {
+                    local: local_timestamp,
+                    remote: remote_timestamp,
+                }));
+            }
+            // No local saves usually means their folder is missing or not mounted
+            // yet, uploading would delete the saves from the target
+            let remote_has_files = remote_manifest
+                .as_ref()
+                .is_some_and(|manifest| !manifest.files.is_empty());
+            if local_files.is_empty() && remote_has_files && !conflict_resolution {
+                log::warn!(
+                    "No local saves of {}, refusing to replace the saves on the target",
+                    app_id
+                );
+                return Err(SyncFailure::Reason(CloudFailureReason::Conflict {
+                    local: local_timestamp,
+                    remote: remote_timestamp,
+                }));
+            }
+            // Files of the save locations that can't be reached here are kept on the target
+            let kept_files = remote_manifest
+                .iter()
+                .flat_map(|manifest| &manifest.files)
+                .filter(|file| {
+                    !save_paths
+                        .iter()
+                        .any(|save_path| save_path.alias == file.alias)
+                })
+                .cloned();
+            let manifest = RemoteManifest {
+                timestamp: local_timestamp,
+                files: local_files
+                    .iter()
+                    .map(|file| file.remote.clone())
+                    .chain(kept_files)
+                    .collect(),
+            };
+            upload(
+                app_id,
+                backend.as_ref(),
+                &config,
+                &local_files,
+                remote_manifest,
+                manifest,
+                on_progress,
+            )?;
+            state.last_synced = local_timestamp;
+        }
+        CloudSyncOperation::Download => {
+            let Some(remote_manifest) = remote_manifest else {
+                log::info!("No saves of {} on the target yet", app_id);
+                on_progress(1.0);
+                return Ok(());
+            };
+            // The local saves changed since we last synced
+            let local_changed = !local_files.is_empty() && local_timestamp > state.last_synced;
+            if local_changed && local_timestamp != remote_timestamp && !conflict_resolution {
+                return Err(SyncFailure::Reason(CloudFailureReason::Conflict {
+                    local: local_timestamp,
+                    remote: remote_timestamp,
+                }));
+            }
+            download(
+                app_id,
+                backend.as_ref(),
+                save_paths,
+                &remote_manifest,
+                on_progress,
+            )?;
+            state.last_synced = remote_timestamp;
+        }
+    }
+
+    save_sync_state(app_id, &state).map_err(|e| {
+        SyncFailure::transfer(operation, format!("Failed to record the sync state: {}", e))
+    })?;
+    Ok(())
+}
+
+fn upload(
+    app_id: &str,
+    backend: &dyn SyncBackend,
+    config: &CloudSyncConfig,
+    local_files: &[LocalFile],
+    remote_manifest: Option<RemoteManifest>,
+    manifest: RemoteManifest,
+    on_progress: &dyn Fn(f64),
+) -> Result<(), SyncFailure> {
+    let previous_files = remote_manifest.map(|m| m.files).unwrap_or_default();
+
+    // The saves of this app replace the previous ones, only the difference counts
+    let upload_size: u64 = local_files.iter().map(|file| file.remote.size).sum();
+    let previous_size: u64 = previous_files.iter().map(|file| file.size).sum();
+    let usage = backend
+        .usage()
+        .map_err(|e| SyncFailure::Error(PluginError::CloudFileUpload, e.to_string()))?;
+    if let Some((used, total)) = usage {
+        let quota = config.quota.unwrap_or(total);
+        let needed = (used + upload_size).saturating_sub(previous_size);
+        if needed > quota {
+            return Err(SyncFailure::Reason(CloudFailureReason::Quota {
+                total: needed,
+                quota,
+            }));
+        }
+    }
+
+    for (index, file) in local_files.iter().enumerate() {
+        let unchanged = previous_files.contains(&file.remote);
+        if !unchanged {
+            let data = fs::read(&file.local_path).map_err(|e| {
+                SyncFailure::transfer(
+                    CloudSyncOperation::Upload,
+                    format!("Can't read {:?}: {}", file.local_path, e),
+                )
+            })?;
+            backend
+                .write(&get_remote_path(app_id, &file.remote), &data)
+                .map_err(|e| to_transfer_failure(e, PluginError::CloudFileUpload))?;
+        }
+        on_progress((index + 1) as f64 / (local_files.len() + 1) as f64);
+    }
+
+    // Write the manifest last, so an interrupted upload leaves the previous saves usable
+    backend
+        .write(
+            &format!("{}/manifest.json", app_id),
+            &serde_json::to_vec(&manifest)
+                .map_err(|e| SyncFailure::transfer(CloudSyncOperation::Upload, e))?,
+        )
+        .map_err(|e| to_transfer_failure(e, PluginError::CloudFileUpload))?;
+
+    for previous_file in previous_files {
+        if !manifest
+            .files
+            .iter()
+            .any(|file| file.alias == previous_file.alias && file.path == previous_file.path)
+        {
+            if let Err(e) = backend.delete(&get_remote_path(app_id, &previous_file)) {
+                log::warn!(
+                    "Failed to delete {} from the target: {}",
+                    previous_file.path,
+                    e
+                );
+            }
+        }
+    }
+    on_progress(1.0);
+    Ok(())
+}
+
+fn download(
+    app_id: &str,
+    backend: &dyn SyncBackend,
+    save_paths: &[CloudPath],
+    manifest: &RemoteManifest,
+    on_progress: &dyn Fn(f64),
+) -> Result<(), SyncFailure> {
+    let mut targets = Vec::new();
+    for file in &manifest.files {
+        let Some(save_path) = save_paths.iter().find(|path| path.alias == file.alias) else {
+            log::warn!(
+                "Skipping {}, no save location named {}",
+                file.path,
+                file.alias
+            );
+            continue;
+        };
+        if file.path.split('/').any(|part| part == "..") {
+            log::warn!(
+                "Skipping {}, it points outside of its save location",
+                file.path
+            );
+            continue;
+        }
+        targets.push((file, Path::new(&save_path.path).join(&file.path)));
+    }
+
+    let needed: u64 = targets.iter().map(|(file, _)| file.size).sum();
+    if let Some((available, _)) = targets
+        .first()
+        .and_then(|(_, path)| path.ancestors().find(|ancestor| ancestor.exists()))
+        .and_then(get_disk_space)
+    {
+        if needed > available {
+            return Err(SyncFailure::Reason(CloudFailureReason::Disk { needed }));
+        }
+    }
+
+    for (index, (file, local_path)) in targets.iter().enumerate() {
+        let data = backend
+            .read(&get_remote_path(app_id, file))
+            .map_err(|e| to_transfer_failure(e, PluginError::CloudFileDownload))?
+            .ok_or(SyncFailure::Error(
+                PluginError::CloudFileDownload,
+                format!("{} is missing from the target", file.path),
+            ))?;
+        write_local_file(local_path, &data, file.modified).map_err(|e| {
+            SyncFailure::transfer(
+                CloudSyncOperation::Download,
+                format!("Can't write {:?}: {}", local_path, e),
+            )
+        })?;
+        on_progress((index + 1) as f64 / targets.len() as f64);
+    }
+    on_progress(1.0);
+    Ok(())
+}
+
+/// Write a downloaded file, keeping the remote modification time so the saves
+/// don't look changed locally
+fn write_local_file(path: &Path, data: &[u8], modified: u64) -> EmptyResult {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)?;
+    fs::File::options()
+        .write(true)
+        .open(path)?
+        .set_modified(UNIX_EPOCH + Duration::from_secs(modified))?;
+    Ok(())
+}
+
+fn to_transfer_failure(
+    error: Box<dyn std::error::Error + Send + Sync>,
+    plugin_error: PluginError,
+) -> SyncFailure {
+    match error.downcast_ref::<PluginError>() {
+        Some(PluginError::CloudQuota) => SyncFailure::Error(
+            PluginError::CloudQuota,
+            "The sync target is out of space".to_string(),
+        ),
+        _ => SyncFailure::Error(plugin_error, error.to_string()),
+    }
+}
+
+fn list_local_files(save_paths: &[CloudPath]) -> ResultWithError<Vec<LocalFile>> {
+    let mut files = Vec::new();
+    for save_path in save_paths {
+        for local_path in list_save_files(save_path)? {
+            let metadata = fs::metadata(&local_path)?;
+            let modified = metadata
+                .modified()
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let relative_path = local_path
+                .strip_prefix(&save_path.path)?
+                .to_string_lossy()
+                .to_string();
+            files.push(LocalFile {
+                remote: RemoteFile {
+                    alias: save_path.alias.clone(),
+                    path: relative_path,
+                    size: metadata.len(),
+                    modified,
+                },
+                local_path,
+            });
+        }
+    }
+    Ok(files)
+}
+
+fn get_remote_path(app_id: &str, file: &RemoteFile) -> String {
+    format!("{}/files/{}/{}", app_id, file.alias, file.path)
+}
+
+fn load_sync_state(app_id: &str) -> SyncState {
+    get_plugin_data_dir(SYNC_STATE_SUBDIR)
+        .ok()
+        .and_then(|dir| fs::read_to_string(dir.join(format!("{}.json", app_id))).ok())
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_sync_state(app_id: &str, state: &SyncState) -> EmptyResult {
+    fs::write(
+        get_plugin_data_dir(SYNC_STATE_SUBDIR)?.join(format!("{}.json", app_id)),
+        serde_json::to_string(state)?,
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    /// Set up a folder target and a save location for the app
+    fn setup(app_id: &str) -> (PathBuf, PathBuf, Vec<CloudPath>) {
+        use_test_data_dir();
+        let root = std::env::temp_dir().join(format!("playtron-sync-{}", std::process::id()));
+        let target = root.join("target");
+        let config_path = get_plugin_data_dir("")
+            .unwrap()
+            .join(CLOUD_SYNC_CONFIG_FILE);
+        fs::write(
+            config_path,
+            format!("target: folder\npath: {}\n", target.to_string_lossy()),
+        )
+        .unwrap();
+        let save_dir = root.join(app_id);
+        let _ = fs::remove_dir_all(&save_dir);
+        fs::create_dir_all(&save_dir).unwrap();
+        let save_paths = vec![CloudPath {
+            alias: "saves".to_string(),
+            path: save_dir.to_string_lossy().to_string(),
+            pattern: "*".to_string(),
+            recursive: true,
+            platforms: vec![],
+        }];
+        (target, save_dir, save_paths)
+    }
+
+    fn write_save(path: &Path, data: &str, modified: u64) {
+        write_local_file(path, data.as_bytes(), modified).unwrap();
+    }
+
+    fn sync(
+        app_id: &str,
+        save_paths: &[CloudPath],
+        operation: CloudSyncOperation,
+        conflict_resolution: bool,
+    ) -> Result<(), SyncFailure> {
+        sync_saves(app_id, save_paths, operation, conflict_resolution, &|_| {})
+    }
+
+    fn is_conflict(result: Result<(), SyncFailure>) -> bool {
+        matches!(
+            result,
+            Err(SyncFailure::Reason(CloudFailureReason::Conflict { .. }))
+        )
+    }
+
+    #[test]
+    fn test_upload_and_download() {
+        let app_id = "sync-roundtrip";
+        let (target, save_dir, save_paths) = setup(app_id);
+        write_save(&save_dir.join("slot1.sav"), "first", 1000);
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, false).is_ok());
+        assert!(target.join(app_id).join("files/saves/slot1.sav").exists());
+
+        fs::remove_file(save_dir.join("slot1.sav")).unwrap();
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Download, false).is_ok());
+        let path = save_dir.join("slot1.sav");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "first");
+        let modified = fs::metadata(&path).unwrap().modified().unwrap();
+        assert_eq!(modified, UNIX_EPOCH + Duration::from_secs(1000));
+    }
+
+    #[test]
+    fn test_conflict() {
+        let app_id = "sync-conflict";
+        let (target, save_dir, save_paths) = setup(app_id);
+        write_save(&save_dir.join("slot1.sav"), "first", 1000);
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, false).is_ok());
+
+        // Another device uploaded newer saves, while the local ones changed too
+        let backend = FolderBackend { root: target };
+        let manifest_path = format!("{}/manifest.json", app_id);
+        let mut manifest: RemoteManifest =
+            serde_json::from_slice(&backend.read(&manifest_path).unwrap().unwrap()).unwrap();
+        manifest.timestamp = 3000;
+        backend
+            .write(&manifest_path, &serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        write_save(&save_dir.join("slot1.sav"), "second", 2000);
+
+        assert!(is_conflict(sync(
+            app_id,
+            &save_paths,
+            CloudSyncOperation::Upload,
+            false
+        )));
+        assert!(is_conflict(sync(
+            app_id,
+            &save_paths,
+            CloudSyncOperation::Download,
+            false
+        )));
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, true).is_ok());
+        // Both sides are in sync again
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, false).is_ok());
+    }
+
+    #[test]
+    fn test_empty_upload_is_refused() {
+        let app_id = "sync-empty";
+        let (target, save_dir, save_paths) = setup(app_id);
+        write_save(&save_dir.join("slot1.sav"), "first", 1000);
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, false).is_ok());
+
+        fs::remove_file(save_dir.join("slot1.sav")).unwrap();
+        let remote_file = target.join(app_id).join("files/saves/slot1.sav");
+        assert!(is_conflict(sync(
+            app_id,
+            &save_paths,
+            CloudSyncOperation::Upload,
+            false
+        )));
+        assert!(remote_file.exists());
+
+        assert!(sync(app_id, &save_paths, CloudSyncOperation::Upload, true).is_ok());
+        assert!(!remote_file.exists());
+    }
+
+    #[test]
+    fn test_folder_write_uses_unique_temporary_files() {
+        let root = std::env::temp_dir().join(format!("playtron-sync-tmp-{}", std::process::id()));
+        let backend = FolderBackend { root: root.clone() };
+        backend.write("slot.tmp", b"tmp").unwrap();
+        backend.write("slot.dat", b"dat").unwrap();
+        assert_eq!(fs::read(root.join("slot.tmp")).unwrap(), b"tmp");
+        assert_eq!(fs::read_dir(&root).unwrap().count(), 2);
+    }
+}
@@ -2,14 +2,19 @@
 /// legendary / gog-warp / etc code should go here. The module can be renamed to represent your
 /// connector more accurately eg `legendary.rs`
 use crate::constants::LIBRARY_SUBDIR;
+use crate::local::move_jobs::is_moving;
+use crate::local::preserve::{backup_preserved_files, get_preserve_patterns};
+use crate::local::sessions::is_running;
+use crate::local::trash::{is_trash_dir, move_to_trash};
+use crate::plugin::errors::PluginError;
 use crate::types::app::InstalledApp;
 use crate::types::results::ResultWithError;
-use crate::utils::disks::get_mount_points;
+use crate::utils::disks::{find_mount_target, get_mount_points, MountTarget};
 use dirs;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Clone)]
@@ -37,11 +42,30 @@ impl LocalConnector {
         Ok(library_paths)
     }
 
+    /// Resolve a MoveItem/Install destination to a library root.
+    /// Block devices and mount roots map to `<mount>/playtron/apps/local`, or to the home
+    /// library for the internal disk. Other paths are used as given.
+    /// The library root is created if it doesn't exist yet.
+    pub fn resolve_library_root(&self, dest_path: &str) -> ResultWithError<PathBuf> {
+        let library_root = match find_mount_target(Path::new(dest_path))? {
+            Some(MountTarget::Internal) => dirs::data_dir()
+                .ok_or("Can't get data dir")?
+                .join(LIBRARY_SUBDIR),
+            Some(MountTarget::External(mount_point)) => mount_point.join(LIBRARY_SUBDIR),
+            None => PathBuf::from(dest_path),
+        };
+        fs::create_dir_all(&library_root)?;
+        Ok(library_root)
+    }
+
     pub async fn list_apps(&self) -> ResultWithError<Vec<String>> {
         let mut app_list = Vec::new();
         for library_path in self.get_library_paths()? {
             for entry in fs::read_dir(library_path)? {
                 let path = entry?.path();
+                if is_trash_dir(&path) {
+                    continue;
+                }
                 if path.is_dir() || path.is_symlink() {
                     app_list.push(
                         path.file_name()
@@ -60,6 +84,9 @@ impl LocalConnector {
         for library_path in self.get_library_paths()? {
             for entry in fs::read_dir(&library_path)? {
                 let path = entry?.path();
+                if is_trash_dir(&path) {
+                    continue;
+                }
                 let dir_name = path
                     .file_name()
                     .ok_or("Failed to read file name")?
@@ -79,9 +106,10 @@ impl LocalConnector {
         for library_path in self.get_library_paths()? {
             for entry in fs::read_dir(library_path)? {
                 let dir_entry = entry?;
-                if dir_entry
-                    .metadata()
-                    .is_ok_and(|metadata| metadata.is_file())
+                if is_trash_dir(&dir_entry.path())
+                    || dir_entry
+                        .metadata()
+                        .is_ok_and(|metadata| metadata.is_file())
                 {
                     continue;
                 }
@@ -153,14 +181,158 @@ impl LocalConnector {
                 return Err(format!("Couldn't find install path for {}", app_id).into());
             }
         };
-        log::info!("Removing {:?}", &install_path);
-        if install_path.exists() {
-            match fs::remove_dir_all(&install_path) {
-                Ok(_) => Ok(()),
-                Err(e) => Err(format!("Failed to remove {:?}: {}", install_path, e).into()),
+        if is_moving(app_id) || is_running(app_id) {
+            log::error!(
+                "Refusing to uninstall {} while it is moved or played",
+                app_id
+            );
+            return Err(PluginError::AppInUse.into());
+        }
+        let library_root = self.get_library_root_of(&install_path)?;
+
+        // A broken or missing gameinfo.yaml shouldn't prevent uninstalling
+        let metadata = self.load_metadata(app_id).await.unwrap_or_default();
+        backup_preserved_files(app_id, &install_path, &get_preserve_patterns(&metadata))?;
+
+        if install_path.is_symlink() {
+            // Only remove the link, the game files it points to aren't ours to delete
+            log::info!("Removing link {:?}", install_path);
+            fs::remove_file(&install_path)?;
+            return Ok(());
+        }
+        // Replacing an earlier trashed copy of the app removes a whole game folder
+        let app_id = app_id.to_string();
+        tokio::task::spawn_blocking(move || move_to_trash(&library_root, &app_id, &install_path))
+            .await?
+    }
+
+    /// Returns the library root directly containing the given app folder.
+    /// Fails with a `Permission` error for any path that isn't strictly inside a
+    /// known library root, to make sure nothing else ever gets removed.
+    fn get_library_root_of(&self, install_path: &Path) -> ResultWithError<PathBuf> {
+        let is_app_folder = install_path
+            .file_name()
+            .is_some_and(|name| name != "." && name != ".." && !is_trash_dir(install_path));
+        // Canonicalize the parent rather than the app folder itself, so symlinked
+        // app folders are checked by where the link lives and not where it points to
+        let parent = install_path
+            .parent()
+            .and_then(|parent| parent.canonicalize().ok());
+        if let (true, Some(parent)) = (is_app_folder, parent) {
+            for library_root in self.get_library_paths()? {
+                if library_root.canonicalize().is_ok_and(|root| root == parent) {
+                    return Ok(library_root);
+                }
             }
-        } else {
-            Ok(())
         }
+        log::error!(
+            "Refusing to remove {:?}, it is outside of the library",
+            install_path
+        );
+        Err(PluginError::Permission.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::move_jobs::start_move_job;
+    use crate::local::sessions::{end_session, start_session};
+    use crate::utils::system::use_test_data_dir;
+
+    /// Create an app folder in the home library and returns its path
+    fn create_app(app_id: &str) -> PathBuf {
+        use_test_data_dir();
+        let install_path = dirs::data_dir().unwrap().join(LIBRARY_SUBDIR).join(app_id);
+        fs::create_dir_all(&install_path).unwrap();
+        fs::write(install_path.join("game.exe"), "").unwrap();
+        install_path
+    }
+
+    fn is_plugin_error(result: ResultWithError<()>, error: PluginError) -> bool {
+        result
+            .err()
+            .and_then(|e| e.downcast_ref::<PluginError>().copied())
+            == Some(error)
+    }
+
+    #[test]
+    fn test_library_root_of_refuses_outside_paths() {
+        let install_path = create_app("connector-root");
+        let library_root = install_path.parent().unwrap().to_path_buf();
+        let connector = LocalConnector;
+
+        assert_eq!(
+            connector.get_library_root_of(&install_path).unwrap(),
+            library_root
+        );
+        let outside =
+            std::env::temp_dir().join(format!("connector-outside-{}", std::process::id()));
+        fs::create_dir_all(outside.join("game")).unwrap();
+        for path in [
+            outside.join("game"),
+            library_root.clone(),
+            library_root.join(".trash"),
+            library_root.join(".."),
+            install_path.join("bin"),
+        ] {
+            let error = connector.get_library_root_of(&path).unwrap_err();
+            assert_eq!(
+                error.downcast_ref::<PluginError>(),
+                Some(&PluginError::Permission),
+                "{:?}",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_moves_to_trash() {
+        let install_path = create_app("connector-trash");
+        LocalConnector.uninstall("connector-trash").await.unwrap();
+        assert!(!install_path.exists());
+        assert!(LocalConnector
+            .find_app("connector-trash")
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_only_removes_links() {
+        use_test_data_dir();
+        let target = std::env::temp_dir().join(format!("connector-target-{}", std::process::id()));
+        fs::create_dir_all(&target).unwrap();
+        fs::write(target.join("game.exe"), "").unwrap();
+        let install_path = dirs::data_dir()
+            .unwrap()
+            .join(LIBRARY_SUBDIR)
+            .join("connector-link");
+        fs::create_dir_all(install_path.parent().unwrap()).unwrap();
+        std::os::unix::fs::symlink(&target, &install_path).unwrap();
+
+        LocalConnector.uninstall("connector-link").await.unwrap();
+        assert!(!install_path.is_symlink());
+        assert!(target.join("game.exe").exists());
+    }
+
+    #[tokio::test]
+    async fn test_uninstall_refuses_apps_in_use() {
+        let install_path = create_app("connector-moving");
+        let job = start_move_job("connector-moving", &install_path, Path::new("/tmp")).unwrap();
+        assert!(is_plugin_error(
+            LocalConnector.uninstall("connector-moving").await,
+            PluginError::AppInUse
+        ));
+        drop(job);
+
+        let install_path = create_app("connector-running");
+        start_session("connector-running", &install_path);
+        assert!(is_plugin_error(
+            LocalConnector.uninstall("connector-running").await,
+            PluginError::AppInUse
+        ));
+        end_session("connector-running");
+        assert!(install_path.exists());
+        LocalConnector.uninstall("connector-running").await.unwrap();
     }
 }
@@ -0,0 +1,683 @@
+//! Launch options of a game, built from its gameinfo.yaml. A game has a single
+//! option built from `executable`, unless it lists several under `launch_options`:
+//!
+//! ```yaml
+//! executable: Game.exe
+//! env:
+//!   DXVK_HUD: fps
+//! launch_options:
+//!   - description: Play
+//!   - description: Safe mode
+//!     env:
+//!       PROTON_USE_WINED3D: 1
+//! ```
+//!
+//! Options inherit `executable`, `arguments` and `env` from the game, their own
+//! values taking precedence. Options can carry `hardware_tags` (e.g. `handheld`)
+//! to be preferred on matching devices.
+//!
+//! `${INSTALL_DIR}`, `${APP_ID}`, `${DATA_DIR}` (a writable folder for the game),
+//! `${HOME}`, `${SAVE_DIR}` (the first save location) and the variables declared
+//! under `vars` are expanded in executables, arguments and environment values.
+//!
+//! Executables can be Windows shortcuts (.lnk), their target, arguments and working
+//! directory are then used. A game without `executable` uses the shortcut at the
+//! root of its folder, when there is only one.
+
+use crate::local::launch_types::{
+    build_launch_target, detect_target_kind, LaunchTarget, TargetKind,
+};
+use crate::types::app::{LaunchOption, LaunchType};
+use crate::types::cloud_sync::CloudPath;
+use crate::types::results::ResultWithError;
+use crate::utils::hardware::{is_valid_hardware_tag, HARDWARE_TAGS};
+use crate::utils::lnk::parse_shortcut;
+use crate::utils::paths::resolve_relative_path;
+use crate::utils::system::get_plugin_data_dir;
+use crate::utils::templates::expand_variables;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_DESCRIPTION: &str = "Launch";
+
+#[derive(Deserialize, Debug, Clone, Default)]
+struct LaunchOptionConfig {
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    executable: Option<String>,
+    #[serde(default)]
+    arguments: Option<String>,
+    #[serde(default)]
+    working_directory: Option<String>,
+    #[serde(default)]
+    hardware_tags: Vec<String>,
+    #[serde(default)]
+    env: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// A launch option along with the file it starts
+pub struct ResolvedLaunchOption {
+    pub launch_option: LaunchOption,
+    /// The file started, unless it is a command from the PATH or wasn't found
+    pub target_path: Option<PathBuf>,
+    /// Unknown when the type of the file couldn't be detected
+    pub target_kind: Option<TargetKind>,
+    /// Why the option may not start, reported when the game is launched
+    pub problem: Option<String>,
+}
+
+/// Returns the launch options of a game, none if it has no executable
+pub fn build_launch_options(
+    app_id: &str,
+    install_path: &Path,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    save_paths: &[CloudPath],
+) -> ResultWithError<Vec<ResolvedLaunchOption>> {
+    let variables = build_variables(app_id, install_path, metadata, save_paths)?;
+    let lookup = |name: &str| {
+        variables
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    };
+
+    let game_executable = metadata
+        .get("executable")
+        .and_then(|exe| exe.as_str())
+        .map(String::from)
+        .or_else(|| find_shortcut(install_path));
+    let game_arguments = metadata
+        .get("arguments")
+        .and_then(|args| args.as_str())
+        .map(String::from);
+    let game_working_directory = metadata
+        .get("working_directory")
+        .and_then(|dir| dir.as_str())
+        .map(String::from);
+    let game_env = match metadata.get("env") {
+        Some(env) => parse_env(
+            serde_yaml::from_value(env.clone())
+                .map_err(|e| format!("Invalid env for {}: {}", app_id, e))?,
+        )?,
+        None => BTreeMap::new(),
+    };
+    let configs: Vec<LaunchOptionConfig> = match metadata.get("launch_options") {
+        Some(options) => serde_yaml::from_value(options.clone())
+            .map_err(|e| format!("Invalid launch_options for {}: {}", app_id, e))?,
+        None if game_executable.is_some() => vec![LaunchOptionConfig::default()],
+        None => Vec::new(),
+    };
+
+    // Option level values can refer to the game level ones
+    let game_env = expand_env(&game_env, &lookup);
+
+    let mut launch_options = Vec::new();
+    for config in configs {
+        let executable = config
+            .executable
+            .as_ref()
+            .or(game_executable.as_ref())
+            .ok_or(format!("A launch option of {} has no executable", app_id))?;
+        let configured_executable = expand_variables(executable, &lookup);
+        let mut arguments = config
+            .arguments
+            .as_ref()
+            .or(game_arguments.as_ref())
+            .map(|args| expand_variables(args, &lookup))
+            .unwrap_or_default();
+        // Problems only affect this option, which is then passed through as configured
+        let mut problem = None;
+        let mut report = |message: String| {
+            log::warn!("Launch option of {}: {}", app_id, message);
+            problem.get_or_insert(message);
+        };
+
+        let mut executable_path = match resolve_executable(install_path, &configured_executable) {
+            Ok(path) => Some(path),
+            // Commands such as `dosbox` are looked up in the PATH when launched
+            Err(_) if is_system_command(&configured_executable) => None,
+            Err(e) => {
+                report(format!("Invalid executable: {}", e));
+                None
+            }
+        };
+        let mut target_kind =
+            executable_path
+                .as_ref()
+                .and_then(|path| match detect_target_kind(path) {
+                    Ok(kind) => Some(kind),
+                    Err(e) => {
+                        report(e.to_string());
+                        None
+                    }
+                });
+        let mut shortcut_working_directory = None;
+        if let (Some(TargetKind::Shortcut), Some(path)) = (target_kind, &executable_path) {
+            match resolve_shortcut(install_path, path) {
+                Ok(shortcut) => {
+                    target_kind = match detect_target_kind(&shortcut.path) {
+                        Ok(kind) => Some(kind),
+                        Err(e) => {
+                            report(format!("Invalid shortcut target: {}", e));
+                            None
+                        }
+                    };
+                    executable_path = Some(shortcut.path);
+                    if !shortcut.arguments.is_empty() {
+                        arguments = format!("{} {}", shortcut.arguments, arguments)
+                            .trim_end()
+                            .to_string();
+                    }
+                    shortcut_working_directory = shortcut.working_directory;
+                }
+                Err(e) => {
+                    report(format!("Invalid shortcut {}: {}", configured_executable, e));
+                    target_kind = None;
+                }
+            }
+        }
+
+        let default_working_directory = get_default_working_directory(
+            install_path,
+            &executable_path
+                .clone()
+                .unwrap_or_else(|| install_path.join(&configured_executable)),
+        );
+        let working_directory = match config
+            .working_directory
+            .as_ref()
+            .or(game_working_directory.as_ref())
+        {
+            Some(dir) => {
+                match resolve_working_directory(install_path, &expand_variables(dir, &lookup)) {
+                    Ok(dir) => dir,
+                    Err(e) => {
+                        report(format!("Invalid working_directory: {}", e));
+                        default_working_directory
+                    }
+                }
+            }
+            None => shortcut_working_directory.unwrap_or(default_working_directory),
+        };
+        // The executable is relative to the working directory when it is inside of it
+        let executable = match &executable_path {
+            Some(path) => match path.strip_prefix(&working_directory) {
+                Ok(relative) if executable.starts_with("./") => {
+                    format!("./{}", relative.to_string_lossy())
+                }
+                Ok(relative) => relative.to_string_lossy().to_string(),
+                Err(_) => path.to_string_lossy().to_string(),
+            },
+            None => configured_executable.clone(),
+        };
+        let target = match target_kind {
+            Some(kind) => match build_launch_target(kind, executable.clone(), arguments.clone()) {
+                Ok(target) => Some(target),
+                Err(e) => {
+                    report(e.to_string());
+                    None
+                }
+            },
+            None => None,
+        }
+        .unwrap_or(LaunchTarget {
+            executable,
+            arguments,
+            launch_type: LaunchType::Game,
+        });
+
+        // A misspelled tag shouldn't prevent launching the game
+        let hardware_tags: Vec<String> = config
+            .hardware_tags
+            .into_iter()
+            .filter(|tag| {
+                let is_valid = is_valid_hardware_tag(tag);
+                if !is_valid {
+                    log::warn!(
+                        "Ignoring unknown hardware tag {} for {}, valid tags are: {}",
+                        tag,
+                        app_id,
+                        HARDWARE_TAGS.join(", ")
+                    );
+                }
+                is_valid
+            })
+            .collect();
+
+        let mut environment = game_env.clone();
+        let option_env = parse_env(config.env)?;
+        environment.extend(expand_env(&option_env, &|name| {
+            game_env.get(name).cloned().or_else(|| lookup(name))
+        }));
+
+        launch_options.push(ResolvedLaunchOption {
+            launch_option: LaunchOption {
+                description: config
+                    .description
+                    .unwrap_or_else(|| DEFAULT_DESCRIPTION.to_string()),
+                executable: target.executable,
+                working_directory: working_directory.to_string_lossy().to_string(),
+                environment: environment.into_iter().collect(),
+                arguments: target.arguments,
+                hardware_tags,
+                launch_type: target.launch_type,
+            },
+            target_path: executable_path,
+            target_kind,
+            problem,
+        });
+    }
+    Ok(launch_options)
+}
+
+/// Returns the built-in variables and the ones declared under `vars`
+fn build_variables(
+    app_id: &str,
+    install_path: &Path,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    save_paths: &[CloudPath],
+) -> ResultWithError<BTreeMap<String, String>> {
+    let data_dir = get_plugin_data_dir(&format!("apps/{}", app_id))?;
+    let home_dir = dirs::home_dir().ok_or("Can't get home dir")?;
+    // Windows save locations only exist inside the Wine prefix
+    let save_dir = save_paths
+        .iter()
+        .find(|save_path| save_path.path.starts_with('/'))
+        .map(|save_path| save_path.path.clone())
+        .unwrap_or_else(|| data_dir.join("saves").to_string_lossy().to_string());
+
+    let mut variables = BTreeMap::from([
+        (
+            "INSTALL_DIR".to_string(),
+            install_path.to_string_lossy().to_string(),
+        ),
+        ("APP_ID".to_string(), app_id.to_string()),
+        (
+            "DATA_DIR".to_string(),
+            data_dir.to_string_lossy().to_string(),
+        ),
+        ("HOME".to_string(), home_dir.to_string_lossy().to_string()),
+        ("SAVE_DIR".to_string(), save_dir),
+    ]);
+
+    let custom_vars = match metadata.get("vars") {
+        Some(vars) => parse_env(
+            serde_yaml::from_value(vars.clone())
+                .map_err(|e| format!("Invalid vars for {}: {}", app_id, e))?,
+        )?,
+        None => BTreeMap::new(),
+    };
+    for (name, value) in custom_vars {
+        if variables.contains_key(&name) {
+            log::warn!(
+                "Ignoring {} in vars of {}, it is a built-in variable",
+                name,
+                app_id
+            );
+            continue;
+        }
+        let value = expand_variables(&value, &|name| {
+            variables
+                .get(name)
+                .cloned()
+                .or_else(|| std::env::var(name).ok())
+        });
+        variables.insert(name, value);
+    }
+    Ok(variables)
+}
+
+/// Returns the absolute path of an executable, matched case-insensitively inside the game folder
+fn resolve_executable(install_path: &Path, executable: &str) -> ResultWithError<PathBuf> {
+    let executable = match Path::new(executable).strip_prefix(install_path) {
+        Ok(relative) => relative.to_string_lossy().to_string(),
+        Err(_) if executable.starts_with('/') => return Ok(PathBuf::from(executable)),
+        Err(_) => executable.to_string(),
+    };
+    Ok(install_path.join(resolve_relative_path(install_path, &executable)?))
+}
+
+/// A shortcut target, with its paths mapped into the game folder
+struct ShortcutTarget {
+    path: PathBuf,
+    arguments: String,
+    working_directory: Option<PathBuf>,
+}
+
+/// Read a Windows shortcut. Its paths point to where the game was installed on
+/// Windows (e.g. `C:\Games\MyGame\game.exe`) and are mapped back into the game folder.
+fn resolve_shortcut(install_path: &Path, shortcut_path: &Path) -> ResultWithError<ShortcutTarget> {
+    let shortcut = parse_shortcut(&fs::read(shortcut_path)?)?;
+    let shortcut_dir = shortcut_path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(install_path).ok())
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // The absolute target also tells which Windows folder was the game folder
+    let absolute_target = shortcut
+        .target
+        .as_ref()
+        .and_then(|target| map_windows_path(install_path, target));
+    let relative_target = shortcut.relative_path.as_ref().and_then(|relative| {
+        resolve_relative_path(install_path, &format!("{}/{}", shortcut_dir, relative)).ok()
+    });
+    let windows_root = absolute_target.as_ref().map(|(_, root)| root.as_slice());
+    let target = relative_target
+        .or_else(|| absolute_target.as_ref().map(|(target, _)| target.clone()))
+        .ok_or_else(|| {
+            format!(
+                "its target {} is not in the game folder",
+                shortcut
+                    .target
+                    .as_ref()
+                    .or(shortcut.relative_path.as_ref())
+                    .cloned()
+                    .unwrap_or_default()
+            )
+        })?;
+
+    let working_directory = shortcut.working_directory.as_ref().and_then(|dir| {
+        let path = map_windows_dir(install_path, windows_root, dir).filter(|path| path.is_dir());
+        if path.is_none() {
+            log::warn!(
+                "Ignoring working directory {} of shortcut {:?}",
+                dir,
+                shortcut_path
+            );
+        }
+        path
+    });
+
+    Ok(ShortcutTarget {
+        path: install_path.join(target),
+        arguments: shortcut.arguments.unwrap_or_default(),
+        working_directory,
+    })
+}
+
+/// Map an absolute Windows path into the game folder, trying the longest trailing part
+/// of the path first. Returns the path relative to the game folder along with the
+/// Windows folder that matched the game folder.
+fn map_windows_path(install_path: &Path, path: &str) -> Option<(String, Vec<String>)> {
+    let components = get_windows_path_components(path);
+    (0..components.len()).find_map(|start| {
+        resolve_relative_path(install_path, &components[start..].join("/"))
+            .ok()
+            .map(|relative| (relative, components[..start].to_vec()))
+    })
+}
+
+/// Map a Windows folder into the game folder, using the game folder found for the target
+fn map_windows_dir(
+    install_path: &Path,
+    windows_root: Option<&[String]>,
+    dir: &str,
+) -> Option<PathBuf> {
+    let components = get_windows_path_components(dir);
+    if let Some(root) = windows_root {
+        let is_in_root = components.len() >= root.len()
+            && components
+                .iter()
+                .zip(root)
+                .all(|(component, root)| component.eq_ignore_ascii_case(root));
+        if is_in_root {
+            let relative = components[root.len()..].join("/");
+            return match relative.is_empty() {
+                true => Some(install_path.to_path_buf()),
+                false => resolve_relative_path(install_path, &relative)
+                    .ok()
+                    .map(|relative| install_path.join(relative)),
+            };
+        }
+    }
+    map_windows_path(install_path, dir).map(|(relative, _)| install_path.join(relative))
+}
+
+/// Split a Windows path into its folders, without the drive letter
+fn get_windows_path_components(path: &str) -> Vec<String> {
+    path.split(['\\', '/'])
+        .filter(|component| !component.is_empty() && !component.ends_with(':'))
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the only shortcut at the root of the game folder, used when the game has no executable
+fn find_shortcut(install_path: &Path) -> Option<String> {
+    let shortcuts: Vec<String> = fs::read_dir(install_path)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.to_lowercase().ends_with(".lnk"))
+        .collect();
+    match shortcuts.as_slice() {
+        [shortcut] => Some(shortcut.clone()),
+        _ => None,
+    }
+}
+
+/// Returns true for a bare command name (e.g. `dosbox`) found in the PATH
+fn is_system_command(executable: &str) -> bool {
+    !executable.contains(['/', '\\'])
+        && std::env::var_os("PATH").is_some_and(|paths| {
+            std::env::split_paths(&paths).any(|dir| dir.join(executable).is_file())
+        })
+}
+
+/// Returns the absolute path of a working directory, relative ones being relative to the game folder
+fn resolve_working_directory(install_path: &Path, dir: &str) -> ResultWithError<PathBuf> {
+    let is_install_path = dir
+        .split(['/', '\\'])
+        .all(|part| part.is_empty() || part == ".");
+    let path = if dir.starts_with('/') {
+        PathBuf::from(dir)
+    } else if is_install_path {
+        install_path.to_path_buf()
+    } else {
+        install_path.join(resolve_relative_path(install_path, dir)?)
+    };
+    if !path.is_dir() {
+        return Err(format!("{} is not a folder", dir).into());
+    }
+    Ok(path)
+}
+
+/// Games are started from the folder of their executable, or from the game folder
+/// when the executable lives outside of it
+fn get_default_working_directory(install_path: &Path, executable_path: &Path) -> PathBuf {
+    match executable_path.parent() {
+        Some(parent) if parent.starts_with(install_path) => parent.to_path_buf(),
+        _ => install_path.to_path_buf(),
+    }
+}
+
+/// Read an `env` or `vars` map, numbers and booleans are turned into strings
+fn parse_env(
+    env: BTreeMap<String, serde_yaml::Value>,
+) -> ResultWithError<BTreeMap<String, String>> {
+    env.into_iter()
+        .map(|(name, value)| {
+            let value = match value {
+                serde_yaml::Value::String(value) => value,
+                serde_yaml::Value::Number(value) => value.to_string(),
+                serde_yaml::Value::Bool(value) => value.to_string(),
+                serde_yaml::Value::Null => String::new(),
+                _ => return Err(format!("Invalid value for {}", name).into()),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+fn expand_env(
+    env: &BTreeMap<String, String>,
+    lookup: &dyn Fn(&str) -> Option<String>,
+) -> BTreeMap<String, String> {
+    env.iter()
+        .map(|(name, value)| (name.clone(), expand_variables(value, lookup)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    fn create_game(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let install_path =
+            std::env::temp_dir().join(format!("launch-options-{}-{}", name, std::process::id()));
+        for (file, content) in files {
+            let path = install_path.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        install_path
+    }
+
+    fn parse_metadata(yaml: &str) -> BTreeMap<String, serde_yaml::Value> {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn test_launch_option_env() {
+        use_test_data_dir();
+        let install_path = create_game("env", &[("Game.exe", b"MZ\x90\x00")]);
+        let metadata = parse_metadata(
+            "executable: Game.exe
+env:
+  DXVK_HUD: fps
+  WINEDLLOVERRIDES: d3d9=n,b
+launch_options:
+  - description: Play
+  - description: Safe mode
+    env:
+      PROTON_USE_WINED3D: 1
+      DXVK_HUD: false
+      WINEDLLOVERRIDES: ${WINEDLLOVERRIDES};dinput8=n,b",
+        );
+        let options = build_launch_options("test-env", &install_path, &metadata, &[]).unwrap();
+        let env = |index: usize| -> BTreeMap<String, String> {
+            options[index]
+                .launch_option
+                .environment
+                .iter()
+                .cloned()
+                .collect()
+        };
+        assert_eq!(
+            env(0),
+            BTreeMap::from([
+                ("DXVK_HUD".to_string(), "fps".to_string()),
+                ("WINEDLLOVERRIDES".to_string(), "d3d9=n,b".to_string()),
+            ])
+        );
+        assert_eq!(
+            env(1),
+            BTreeMap::from([
+                ("DXVK_HUD".to_string(), "false".to_string()),
+                ("PROTON_USE_WINED3D".to_string(), "1".to_string()),
+                (
+                    "WINEDLLOVERRIDES".to_string(),
+                    "d3d9=n,b;dinput8=n,b".to_string()
+                ),
+            ])
+        );
+
+        let metadata = parse_metadata("executable: Game.exe\nenv:\n  NESTED: [1, 2]");
+        assert!(build_launch_options("test-env", &install_path, &metadata, &[]).is_err());
+    }
+
+    #[test]
+    fn test_template_variables() {
+        use_test_data_dir();
+        let install_path = create_game("templates", &[("bin/game", b"\x7fELF\x02\x01\x01\x00")]);
+        let metadata = parse_metadata(
+            "executable: ${INSTALL_DIR}/bin/game
+vars:
+  PROFILE: ${DATA_DIR}/profile
+  APP_ID: ignored
+arguments: -datadir=${INSTALL_DIR}/data -profile=${PROFILE} -id=$APP_ID
+env:
+  SAVES: ${SAVE_DIR}",
+        );
+        let save_paths = [CloudPath {
+            alias: "saves".to_string(),
+            path: "/saves/templates".to_string(),
+            pattern: "*".to_string(),
+            recursive: true,
+            platforms: vec![],
+        }];
+        let options =
+            build_launch_options("test-templates", &install_path, &metadata, &save_paths).unwrap();
+        let data_dir = get_plugin_data_dir("apps/test-templates").unwrap();
+        let launch_option = &options[0].launch_option;
+        assert_eq!(launch_option.executable, "game");
+        assert_eq!(
+            launch_option.arguments,
+            format!(
+                "-datadir={}/data -profile={}/profile -id=test-templates",
+                install_path.display(),
+                data_dir.display()
+            )
+        );
+        assert_eq!(
+            launch_option.environment,
+            vec![("SAVES".to_string(), "/saves/templates".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_undetected_executables_are_passed_through() {
+        use_test_data_dir();
+        let install_path = create_game(
+            "undetected",
+            &[
+                ("Game.exe", b"MZ\x90\x00"),
+                ("start.sh", b"echo no shebang\n"),
+            ],
+        );
+        let metadata = parse_metadata(
+            "executable: Game.exe
+launch_options:
+  - description: Play
+  - description: Script
+    executable: ./start.sh
+  - description: Missing
+    executable: missing.exe",
+        );
+        let options =
+            build_launch_options("test-undetected", &install_path, &metadata, &[]).unwrap();
+        assert_eq!(options.len(), 3);
+
+        assert!(options[0].problem.is_none());
+        assert_eq!(options[0].target_kind, Some(TargetKind::WindowsExecutable));
+
+        assert!(options[1].problem.is_some());
+        assert_eq!(options[1].target_kind, None);
+        assert_eq!(options[1].launch_option.executable, "./start.sh");
+        assert!(matches!(
+            options[1].launch_option.launch_type,
+            LaunchType::Game
+        ));
+
+        assert!(options[2].problem.is_some());
+        assert_eq!(options[2].target_path, None);
+        assert_eq!(options[2].launch_option.executable, "missing.exe");
+    }
+
+    #[test]
+    fn test_system_commands_are_not_problems() {
+        use_test_data_dir();
+        let install_path = create_game("system-command", &[]);
+        let metadata = parse_metadata("executable: sh\narguments: -c true");
+        let options =
+            build_launch_options("test-system-command", &install_path, &metadata, &[]).unwrap();
+        assert!(options[0].problem.is_none());
+        assert_eq!(options[0].target_path, None);
+        assert_eq!(options[0].launch_option.executable, "sh");
+        assert_eq!(options[0].launch_option.arguments, "-c true");
+    }
+}
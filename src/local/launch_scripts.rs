@@ -0,0 +1,174 @@
+//! Commands declared in gameinfo.yaml to run around each game session, e.g.
+//!
+//! ```yaml
+//! pre_launch: ./start-server.sh
+//! post_launch:
+//!   command: ./cleanup.sh --all
+//!   timeout: 120
+//! ```
+//!
+//! Commands are run with `sh -c` from the game folder. Their output is written
+//! to a log file in the plugin data folder, so a command that starts a
+//! background process (e.g. `./server &`) doesn't block the launch.
+
+use crate::constants::LAUNCH_SCRIPT_TIMEOUT_SECS;
+use crate::types::results::EmptyResult;
+use crate::utils::system::get_plugin_data_dir;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::process::Command;
+
+const LOGS_SUBDIR: &str = "logs";
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+enum LaunchScript {
+    Command(String),
+    Config {
+        command: String,
+        /// In seconds
+        #[serde(default = "default_timeout")]
+        timeout: u64,
+    },
+}
+
+fn default_timeout() -> u64 {
+    LAUNCH_SCRIPT_TIMEOUT_SECS
+}
+
+/// When a launch script runs, also the gameinfo.yaml key it is read from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LaunchStage {
+    PreLaunch,
+    PostLaunch,
+}
+
+impl LaunchStage {
+    fn key(&self) -> &'static str {
+        match self {
+            LaunchStage::PreLaunch => "pre_launch",
+            LaunchStage::PostLaunch => "post_launch",
+        }
+    }
+}
+
+/// Run the command declared for the given stage, if any.
+/// Fails if the command can't be started, times out or exits with an error.
+pub async fn run_launch_script(
+    app_id: &str,
+    install_path: &Path,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    stage: LaunchStage,
+) -> EmptyResult {
+    let Some(script) = metadata.get(stage.key()) else {
+        return Ok(());
+    };
+    let (command, timeout) = match serde_yaml::from_value(script.clone())
+        .map_err(|e| format!("Invalid {}: {}", stage.key(), e))?
+    {
+        LaunchScript::Command(command) => (command, LAUNCH_SCRIPT_TIMEOUT_SECS),
+        LaunchScript::Config { command, timeout } => (command, timeout),
+    };
+
+    let log_path =
+        get_plugin_data_dir(LOGS_SUBDIR)?.join(format!("{}-{}.log", app_id, stage.key()));
+    let log_file = fs::File::create(&log_path)?;
+    log::info!(
+        "Running {} command of {}: {} (log: {:?})",
+        stage.key(),
+        app_id,
+        command,
+        log_path
+    );
+
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(&command)
+        .current_dir(install_path)
+        .env("INSTALL_DIR", install_path)
+        .env("APP_ID", app_id)
+        .stdin(Stdio::null())
+        .stdout(log_file.try_clone()?)
+        .stderr(log_file)
+        .kill_on_drop(true)
+        // In its own process group, to stop what the command started along with it
+        .process_group(0)
+        .spawn()?;
+
+    let status = match tokio::time::timeout(Duration::from_secs(timeout), child.wait()).await {
+        Ok(status) => status?,
+        Err(_) => {
+            if let Some(pid) = child.id() {
+                // SAFETY: killpg has no memory safety requirements
+                unsafe { libc::killpg(pid as libc::pid_t, libc::SIGKILL) };
+            }
+            child.kill().await.ok();
+            log_output(&log_path);
+            return Err(format!(
+                "The {} command timed out after {} seconds",
+                stage.key(),
+                timeout
+            )
+            .into());
+        }
+    };
+    log_output(&log_path);
+    if !status.success() {
+        return Err(format!(
+            "The {} command failed ({}), see {:?}",
+            stage.key(),
+            status,
+            log_path
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn log_output(log_path: &Path) {
+    match fs::read(log_path) {
+        Ok(output) => {
+            let output = String::from_utf8_lossy(&output);
+            if !output.trim().is_empty() {
+                log::info!("{}", output.trim_end());
+            }
+        }
+        Err(e) => log::warn!("Failed to read {:?}: {}", log_path, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    #[tokio::test]
+    async fn test_timeout_kills_started_processes() {
+        use_test_data_dir();
+        let install_path =
+            std::env::temp_dir().join(format!("playtron-script-{}", std::process::id()));
+        fs::create_dir_all(&install_path).unwrap();
+        let metadata: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(
+            "pre_launch:\n  command: sleep 30 & echo $! > server.pid; sleep 30\n  timeout: 1\n",
+        )
+        .unwrap();
+
+        let result = run_launch_script(
+            "script-timeout",
+            &install_path,
+            &metadata,
+            LaunchStage::PreLaunch,
+        )
+        .await;
+        assert!(result.is_err());
+        let pid = fs::read_to_string(install_path.join("server.pid")).unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The background process was killed (and reaped by init, or left as a zombie)
+        let status = fs::read_to_string(format!("/proc/{}/stat", pid.trim())).unwrap_or_default();
+        assert!(status.is_empty() || status.contains(") Z "));
+    }
+}
@@ -0,0 +1,209 @@
+//! Detection of what a launch option starts, from the extension and the first
+//! bytes of its executable, to set the launch type and wrap the targets that
+//! can't be run directly (e.g. `java -jar game.jar`).
+
+use crate::types::app::LaunchType;
+use crate::types::results::ResultWithError;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+const PE_MAGIC: &[u8] = b"MZ";
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const SHEBANG: &[u8] = b"#!";
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+/// Stored at offset 8 of type 2 AppImages
+const APPIMAGE_MAGIC: &[u8] = b"AI\x02";
+/// Compound File Binary, the container of .msi files
+const MSI_MAGIC: &[u8] = b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1";
+const LNK_MAGIC: &[u8] = b"\x4c\x00\x00\x00\x01\x14\x02\x00";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetKind {
+    /// PE executable, run with Proton/Wine
+    WindowsExecutable,
+    /// ELF binary
+    LinuxExecutable,
+    AppImage,
+    /// Script starting with a shebang
+    Script,
+    /// Windows batch file, run with Proton/Wine
+    Batch,
+    /// Java archive, run with `java -jar`
+    Jar,
+    /// Windows installer, run with `msiexec /i`
+    Msi,
+    /// Windows shortcut, resolved into its target
+    Shortcut,
+}
+
+/// What to actually run for a launch option
+pub struct LaunchTarget {
+    pub executable: String,
+    pub arguments: String,
+    pub launch_type: LaunchType,
+}
+
+/// Classify an executable by its signature, falling back to its extension for
+/// the text based formats. Fails for files that can't be launched.
+pub fn detect_target_kind(path: &Path) -> ResultWithError<TargetKind> {
+    let mut header = [0; 16];
+    let read = fs::File::open(path)?.read(&mut header)?;
+    let header = &header[..read];
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let kind = if header.starts_with(ELF_MAGIC) {
+        match header.get(8..11) == Some(APPIMAGE_MAGIC) || extension == "appimage" {
+            true => TargetKind::AppImage,
+            false => TargetKind::LinuxExecutable,
+        }
+    } else if header.starts_with(PE_MAGIC) {
+        TargetKind::WindowsExecutable
+    } else if header.starts_with(LNK_MAGIC) {
+        TargetKind::Shortcut
+    } else if header.starts_with(MSI_MAGIC) && extension == "msi" {
+        TargetKind::Msi
+    } else if header.starts_with(ZIP_MAGIC) && extension == "jar" {
+        TargetKind::Jar
+    } else if header.starts_with(SHEBANG) {
+        TargetKind::Script
+    } else if extension == "bat" || extension == "cmd" {
+        TargetKind::Batch
+    } else {
+        return Err(format!(
+            "{:?} is not a supported executable (Windows or Linux executable, AppImage, \
+             script, batch file, .jar, .msi or .lnk)",
+            path.file_name().unwrap_or_default()
+        )
+        .into());
+    };
+    Ok(kind)
+}
+
+/// Build what to run for an executable of the given kind, `executable` being
+/// the path to pass to the launcher and `arguments` the configured arguments.
+pub fn build_launch_target(
+    kind: TargetKind,
+    executable: String,
+    arguments: String,
+) -> ResultWithError<LaunchTarget> {
+    let wrap = |wrapper: &str, prefix: &str, launch_type: LaunchType| LaunchTarget {
+        executable: wrapper.to_string(),
+        arguments: join_arguments(&[prefix, &quote_argument(&executable), &arguments]),
+        launch_type,
+    };
+    Ok(match kind {
+        TargetKind::Jar => wrap("java", "-jar", LaunchType::Game),
+        TargetKind::Msi => wrap("msiexec", "/i", LaunchType::Tool),
+        // Shortcuts are replaced by their target before getting here
+        TargetKind::Shortcut => {
+            return Err("Shortcuts must point to an executable, not to another shortcut".into());
+        }
+        TargetKind::WindowsExecutable
+        | TargetKind::LinuxExecutable
+        | TargetKind::AppImage
+        | TargetKind::Script
+        | TargetKind::Batch => LaunchTarget {
+            executable,
+            arguments,
+            launch_type: LaunchType::Game,
+        },
+    })
+}
+
+/// Quote an argument containing whitespace or quotes, so it is passed as a single argument
+pub fn quote_argument(argument: &str) -> String {
+    if argument.is_empty() || argument.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", argument.replace('"', "\\\""))
+    } else {
+        argument.to_string()
+    }
+}
+
+fn join_arguments(arguments: &[&str]) -> String {
+    arguments
+        .iter()
+        .filter(|argument| !argument.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn write_file(name: &str, content: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("launch-types-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_detect_target_kind() {
+        let mut appimage = ELF_MAGIC.to_vec();
+        appimage.extend_from_slice(b"\x02\x01\x01\x00AI\x02");
+        let cases: &[(&str, &[u8], TargetKind)] = &[
+            ("game.exe", b"MZ\x90\x00", TargetKind::WindowsExecutable),
+            // The content wins over the extension
+            ("game.bin", b"MZ\x90\x00", TargetKind::WindowsExecutable),
+            (
+                "game",
+                b"\x7fELF\x02\x01\x01\x00",
+                TargetKind::LinuxExecutable,
+            ),
+            (
+                "Game.AppImage",
+                b"\x7fELF\x02\x01\x01\x00",
+                TargetKind::AppImage,
+            ),
+            ("game", &appimage, TargetKind::AppImage),
+            ("start.sh", b"#!/bin/sh\n", TargetKind::Script),
+            ("start.BAT", b"@echo off\r\n", TargetKind::Batch),
+            ("start.cmd", b"game.exe\r\n", TargetKind::Batch),
+            ("game.jar", b"PK\x03\x04\x14\x00", TargetKind::Jar),
+            ("setup.msi", MSI_MAGIC, TargetKind::Msi),
+            ("Play.lnk", LNK_MAGIC, TargetKind::Shortcut),
+        ];
+        for (name, content, kind) in cases {
+            let path = write_file(name, content);
+            assert_eq!(detect_target_kind(&path).unwrap(), *kind, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_detect_target_kind_unsupported() {
+        for (name, content) in [
+            ("start.sh", b"echo no shebang\n".as_slice()),
+            ("archive.zip", b"PK\x03\x04\x14\x00"),
+            ("document.doc", MSI_MAGIC),
+            ("empty.exe", b""),
+        ] {
+            let path = write_file(name, content);
+            assert!(detect_target_kind(&path).is_err(), "{}", name);
+        }
+        assert!(detect_target_kind(Path::new("/nonexistent/game.exe")).is_err());
+    }
+
+    #[test]
+    fn test_build_launch_target() {
+        let target =
+            build_launch_target(TargetKind::Jar, "my game.jar".into(), "-w".into()).unwrap();
+        assert_eq!(target.executable, "java");
+        assert_eq!(target.arguments, "-jar \"my game.jar\" -w");
+        assert!(matches!(target.launch_type, LaunchType::Game));
+
+        let target = build_launch_target(TargetKind::Msi, "setup.msi".into(), "".into()).unwrap();
+        assert_eq!(target.executable, "msiexec");
+        assert_eq!(target.arguments, "/i setup.msi");
+        assert!(matches!(target.launch_type, LaunchType::Tool));
+
+        assert!(build_launch_target(TargetKind::Shortcut, "a.lnk".into(), "".into()).is_err());
+    }
+}
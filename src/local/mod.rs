@@ -1,3 +1,18 @@
+pub mod cloud_sync;
 pub mod connector;
+pub mod launch_options;
+pub mod launch_scripts;
+pub mod launch_types;
+pub mod move_jobs;
+pub mod move_journal;
+pub mod permissions;
+pub mod pre_launch;
+pub mod preserve;
+pub mod save_manifest;
+pub mod save_paths;
+pub mod save_snapshots;
 pub mod service;
+pub mod sessions;
+pub mod trash;
 pub mod watcher;
+pub mod webdav;
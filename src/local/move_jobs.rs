@@ -0,0 +1,168 @@
+//! Registry of the app move operations currently running, keyed by app id.
+//! Moves to different disks can run at the same time, while only one move
+//! per app and per destination disk is allowed.
+
+use crate::types::app::{MoveState, MoveStatus};
+use crate::types::results::ResultWithError;
+use crate::utils::disks::get_device_id;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio_util::sync::CancellationToken;
+
+lazy_static::lazy_static! {
+    static ref MOVE_JOBS: Mutex<HashMap<String, MoveJob>> = Mutex::default();
+}
+
+struct MoveJob {
+    source: PathBuf,
+    destination: PathBuf,
+    source_device: Option<u64>,
+    destination_device: Option<u64>,
+    progress: f32,
+    cancel_token: CancellationToken,
+}
+
+impl MoveJob {
+    /// When a disk is unmounted, its paths either vanish or fall back to the parent
+    /// filesystem, so the device id no longer matches the one recorded at start.
+    fn is_source_removed(&self) -> bool {
+        get_device_id(&self.source) != self.source_device
+    }
+
+    fn is_destination_removed(&self) -> bool {
+        get_device_id(&self.destination) != self.destination_device
+    }
+}
+
+/// Keeps a move job registered for as long as it is alive.
+/// The job is removed from the registry when the guard is dropped, whether
+/// the move completed, failed or was cancelled.
+pub struct MoveJobGuard {
+    app_id: String,
+    cancel_token: CancellationToken,
+}
+
+impl MoveJobGuard {
+    pub fn cancel_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    /// True if the source or the destination disk was removed during the move
+    pub fn is_disk_removed(&self) -> bool {
+        MOVE_JOBS
+            .lock()
+            .get(&self.app_id)
+            .is_some_and(|job| job.is_source_removed() || job.is_destination_removed())
+    }
+
+    /// True if the destination disk was removed during the move
+    pub fn is_destination_removed(&self) -> bool {
+        MOVE_JOBS
+            .lock()
+            .get(&self.app_id)
+            .is_some_and(|job| job.is_destination_removed())
+    }
+
+    /// Record the progress of the move, from 0.0 to 1.0
+    pub fn set_progress(&self, progress: f32) {
+        if let Some(job) = MOVE_JOBS.lock().get_mut(&self.app_id) {
+            job.progress = progress;
+        }
+    }
+}
+
+impl Drop for MoveJobGuard {
+    fn drop(&mut self) {
+        MOVE_JOBS.lock().remove(&self.app_id);
+    }
+}
+
+/// Register a new move job, failing if the app is already being moved or if
+/// another move is already writing to the same destination disk.
+pub fn start_move_job(
+    app_id: &str,
+    source: &Path,
+    destination: &Path,
+) -> ResultWithError<MoveJobGuard> {
+    let source_device = get_device_id(source);
+    let destination_device = get_device_id(destination);
+    let mut jobs = MOVE_JOBS.lock();
+    if jobs.contains_key(app_id) {
+        return Err(format!("{} is already being moved", app_id).into());
+    }
+    if destination_device.is_some()
+        && jobs
+            .values()
+            .any(|job| job.destination_device == destination_device)
+    {
+        return Err("An app move to this disk is already in progress".into());
+    }
+
+    let cancel_token = CancellationToken::new();
+    jobs.insert(
+        app_id.to_string(),
+        MoveJob {
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            source_device,
+            destination_device,
+            progress: 0.0,
+            cancel_token: cancel_token.clone(),
+        },
+    );
+    Ok(MoveJobGuard {
+        app_id: app_id.to_string(),
+        cancel_token,
+    })
+}
+
+/// Cancel the move of the given app. Returns false if the app isn't being moved.
+pub fn cancel_move_job(app_id: &str) -> bool {
+    match MOVE_JOBS.lock().get(app_id) {
+        Some(job) => {
+            job.cancel_token.cancel();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Cancel the moves whose source or destination disk is no longer mounted
+pub fn cancel_move_jobs_on_removed_disks() {
+    for (app_id, job) in MOVE_JOBS.lock().iter() {
+        if !job.cancel_token.is_cancelled()
+            && (job.is_source_removed() || job.is_destination_removed())
+        {
+            log::warn!("Disk removed during the move of {}, cancelling", app_id);
+            job.cancel_token.cancel();
+        }
+    }
+}
+
+pub fn is_moving(app_id: &str) -> bool {
+    MOVE_JOBS.lock().contains_key(app_id)
+}
+
+pub fn get_move_status(app_id: &str) -> MoveStatus {
+    match MOVE_JOBS.lock().get(app_id) {
+        Some(job) => MoveStatus {
+            app_id: app_id.to_string(),
+            state: if job.cancel_token.is_cancelled() {
+                MoveState::Cancelling
+            } else {
+                MoveState::Moving
+            },
+            source_path: job.source.to_string_lossy().to_string(),
+            destination_path: job.destination.to_string_lossy().to_string(),
+            progress: job.progress,
+        },
+        None => MoveStatus {
+            app_id: app_id.to_string(),
+            state: MoveState::Idle,
+            source_path: "".to_string(),
+            destination_path: "".to_string(),
+            progress: 0.0,
+        },
+    }
+}
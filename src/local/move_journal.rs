@@ -0,0 +1,326 @@
+//! On-disk journal of the app moves in progress, used to recover moves that
+//! were interrupted by a crash, a power loss or a plugin restart.
+
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::disks::get_device_id;
+use crate::utils::system::get_plugin_data_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+
+const JOURNAL_SUBDIR: &str = "moves";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveStage {
+    /// Files are being copied (or renamed) to the destination, the source is intact
+    Copying,
+    /// The destination is complete and the source is being removed, or the folder
+    /// was renamed to the destination
+    RemovingSource,
+    /// The move failed while the destination disk was unplugged, the partial copy
+    /// must be removed once the disk is available again
+    RollingBack,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MoveJournalEntry {
+    pub app_id: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub stage: MoveStage,
+    /// Device of the library holding the source, to tell a source that is gone
+    /// from one whose disk is not mounted
+    #[serde(default)]
+    pub source_device: Option<u64>,
+}
+
+/// What to do with a move that was interrupted
+#[derive(Debug, PartialEq)]
+pub enum Recovery {
+    /// Remove the partial copy of a move whose failure was already reported
+    RemovePartialCopy,
+    /// The destination is complete, remove what is left of the source
+    Complete,
+    /// Copy the files missing from the destination
+    Resume,
+    /// Drop the partial copy and keep the app where it was
+    RollBack,
+    /// A disk of the move is not mounted, try again later
+    Wait,
+}
+
+impl MoveJournalEntry {
+    /// Returns true when the library holding the source is mounted, on the disk it was on
+    pub fn is_source_mounted(&self) -> bool {
+        let Some(library_root) = self.source.parent() else {
+            return false;
+        };
+        library_root.exists()
+            && self.source_device.is_some()
+            && get_device_id(library_root) == self.source_device
+    }
+
+    /// Decide how to recover the move. A missing source only means that the move
+    /// finished when its library is mounted, not when its disk is unplugged.
+    pub fn plan_recovery(&self, source_mounted: bool, destination_available: bool) -> Recovery {
+        if self.stage == MoveStage::RollingBack {
+            return match destination_available {
+                true => Recovery::RemovePartialCopy,
+                false => Recovery::Wait,
+            };
+        }
+        if !source_mounted {
+            return Recovery::Wait;
+        }
+        match self.stage {
+            MoveStage::RemovingSource => Recovery::Complete,
+            _ if self.source.exists() && destination_available => Recovery::Resume,
+            // Renamed right before the stage could be recorded
+            _ if !self.source.exists() && self.destination.exists() => Recovery::Complete,
+            _ => Recovery::RollBack,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct MoveJournal {
+    path: PathBuf,
+    entry: MoveJournalEntry,
+}
+
+impl MoveJournal {
+    /// Record the start of a move. Fails when a journal already exists for the app,
+    /// its earlier move must be recovered or rolled back first.
+    pub fn create(app_id: &str, source: PathBuf, destination: PathBuf) -> ResultWithError<Self> {
+        let path = get_journal_path(app_id)?;
+        if path.exists() {
+            return Err(format!("A previous move of {} has not been recovered yet", app_id).into());
+        }
+        let source_device = get_device_id(source.parent().unwrap_or(&source));
+        let journal = MoveJournal {
+            path,
+            entry: MoveJournalEntry {
+                app_id: app_id.to_string(),
+                source,
+                destination,
+                stage: MoveStage::Copying,
+                source_device,
+            },
+        };
+        journal.write()?;
+        Ok(journal)
+    }
+
+    /// Reopen the journal of an interrupted move
+    pub fn open(entry: MoveJournalEntry) -> ResultWithError<Self> {
+        Ok(MoveJournal {
+            path: get_journal_path(&entry.app_id)?,
+            entry,
+        })
+    }
+
+    pub fn set_stage(&mut self, stage: MoveStage) -> EmptyResult {
+        self.entry.stage = stage;
+        self.write()
+    }
+
+    /// Remove the journal once the move has either completed or been rolled back
+    pub fn remove(self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            log::warn!("Failed to remove move journal {:?}: {}", self.path, e);
+        }
+    }
+
+    /// Write the journal to a temporary file first and rename it over the previous
+    /// one so a crash never leaves a half written journal behind.
+    fn write(&self) -> EmptyResult {
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(serde_json::to_string(&self.entry)?.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Returns the journal of the app, if it has a move in progress or to roll back
+pub fn read_journal(app_id: &str) -> ResultWithError<Option<MoveJournalEntry>> {
+    let path = get_journal_path(app_id)?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_str(&fs::read_to_string(&path)?)?))
+}
+
+/// Returns the moves that were still in progress when the plugin last stopped
+pub fn list_interrupted_moves() -> ResultWithError<Vec<MoveJournalEntry>> {
+    let mut entries = Vec::new();
+    for dir_entry in fs::read_dir(get_plugin_data_dir(JOURNAL_SUBDIR)?)? {
+        let path = dir_entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                log::error!("Discarding unreadable move journal {:?}: {}", path, e);
+                let _ = fs::remove_file(&path);
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn get_journal_path(app_id: &str) -> ResultWithError<PathBuf> {
+    Ok(get_plugin_data_dir(JOURNAL_SUBDIR)?.join(format!("{}.json", app_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    #[test]
+    fn test_journal_stages() {
+        use_test_data_dir();
+        let app_id = "journal-stages";
+        let mut journal =
+            MoveJournal::create(app_id, PathBuf::from("/a/game"), PathBuf::from("/b/game"))
+                .unwrap();
+        let entry = read_journal(app_id).unwrap().unwrap();
+        assert_eq!(entry.stage, MoveStage::Copying);
+        assert_eq!(entry.destination, PathBuf::from("/b/game"));
+
+        journal.set_stage(MoveStage::RemovingSource).unwrap();
+        assert_eq!(
+            read_journal(app_id).unwrap().unwrap().stage,
+            MoveStage::RemovingSource
+        );
+        assert!(list_interrupted_moves()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.app_id == app_id));
+
+        journal.remove();
+        assert!(read_journal(app_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_create_refuses_existing_journal() {
+        use_test_data_dir();
+        let app_id = "journal-existing";
+        let mut journal =
+            MoveJournal::create(app_id, PathBuf::from("/a/game"), PathBuf::from("/b/game"))
+                .unwrap();
+        journal.set_stage(MoveStage::RollingBack).unwrap();
+        assert!(
+            MoveJournal::create(app_id, PathBuf::from("/a/game"), PathBuf::from("/c/game"))
+                .is_err()
+        );
+        assert_eq!(
+            read_journal(app_id).unwrap().unwrap().destination,
+            PathBuf::from("/b/game")
+        );
+
+        let entry = read_journal(app_id).unwrap().unwrap();
+        MoveJournal::open(entry).unwrap().remove();
+        MoveJournal::create(app_id, PathBuf::from("/a/game"), PathBuf::from("/c/game"))
+            .unwrap()
+            .remove();
+    }
+
+    /// Entry of a move between two library folders, creating the listed folders
+    fn recovery_entry(name: &str, stage: MoveStage, folders: &[&str]) -> MoveJournalEntry {
+        let dir =
+            std::env::temp_dir().join(format!("move-recovery-{}-{}", name, std::process::id()));
+        for folder in ["source", "destination"].iter().chain(folders) {
+            fs::create_dir_all(dir.join(folder)).unwrap();
+        }
+        MoveJournalEntry {
+            app_id: name.to_string(),
+            source: dir.join("source/game"),
+            destination: dir.join("destination/game"),
+            stage,
+            source_device: get_device_id(&dir.join("source")),
+        }
+    }
+
+    #[test]
+    fn test_is_source_mounted() {
+        let mut entry = recovery_entry("mounted", MoveStage::Copying, &[]);
+        assert!(entry.is_source_mounted());
+
+        // Another disk is mounted at the library folder
+        entry.source_device = entry.source_device.map(|device| device + 1);
+        assert!(!entry.is_source_mounted());
+        // Journals without a device can't tell
+        entry.source_device = None;
+        assert!(!entry.is_source_mounted());
+
+        let mut entry = recovery_entry("unmounted", MoveStage::Copying, &[]);
+        entry.source = entry.source.with_file_name("missing-library").join("game");
+        assert!(!entry.is_source_mounted());
+    }
+
+    #[test]
+    fn test_recovery_of_rollbacks() {
+        let entry = recovery_entry("rollback", MoveStage::RollingBack, &["destination/game"]);
+        assert_eq!(entry.plan_recovery(true, true), Recovery::RemovePartialCopy);
+        assert_eq!(
+            entry.plan_recovery(false, true),
+            Recovery::RemovePartialCopy
+        );
+        assert_eq!(entry.plan_recovery(true, false), Recovery::Wait);
+    }
+
+    #[test]
+    fn test_recovery_of_finished_copies() {
+        let entry = recovery_entry("removing", MoveStage::RemovingSource, &["source/game"]);
+        assert_eq!(entry.plan_recovery(true, true), Recovery::Complete);
+        // The rest of the source can't be removed yet
+        assert_eq!(entry.plan_recovery(false, true), Recovery::Wait);
+
+        // Renamed before the stage was recorded
+        let entry = recovery_entry("renamed", MoveStage::Copying, &["destination/game"]);
+        assert_eq!(entry.plan_recovery(true, true), Recovery::Complete);
+    }
+
+    #[test]
+    fn test_recovery_of_unmounted_source() {
+        // The source disk is unplugged, the destination only holds a partial copy
+        let entry = recovery_entry("unplugged", MoveStage::Copying, &["destination/game"]);
+        assert_eq!(entry.plan_recovery(false, true), Recovery::Wait);
+    }
+
+    #[test]
+    fn test_recovery_of_interrupted_copies() {
+        let entry = recovery_entry(
+            "interrupted",
+            MoveStage::Copying,
+            &["source/game", "destination/game"],
+        );
+        assert_eq!(entry.plan_recovery(true, true), Recovery::Resume);
+        assert_eq!(entry.plan_recovery(true, false), Recovery::RollBack);
+
+        // Neither the source nor a copy is left
+        let entry = recovery_entry("lost", MoveStage::Copying, &[]);
+        assert_eq!(entry.plan_recovery(true, true), Recovery::RollBack);
+    }
+
+    #[test]
+    fn test_unreadable_journal_is_discarded() {
+        use_test_data_dir();
+        let path = get_journal_path("journal-unreadable").unwrap();
+        fs::write(&path, "{").unwrap();
+        assert!(!list_interrupted_moves()
+            .unwrap()
+            .iter()
+            .any(|entry| entry.app_id == "journal-unreadable"));
+        assert!(!path.exists());
+    }
+}
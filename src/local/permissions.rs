@@ -0,0 +1,218 @@
+//! Repair of execute permissions on Linux games. Copying a game from Windows
+//! (e.g. with rsync through Cygwin) often drops the execute bits, and the game
+//! then fails to start.
+
+use crate::local::launch_options::build_launch_options;
+use crate::types::results::ResultWithError;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const SHEBANG: &[u8] = b"#!";
+
+/// How much of the game folder to check
+pub enum RepairScope {
+    /// Every file of the game folder
+    Full,
+    /// The executable and the files next to it, quick enough to run on each launch
+    Executable,
+}
+
+/// Add the missing execute bits to the executables of the launch options of a
+/// Linux game, if they are ELF binaries or shebang scripts, and to the ELF
+/// binaries and libraries of the game folder. Returns the files that were changed.
+pub fn repair_exec_permissions(
+    app_id: &str,
+    install_path: &Path,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    scope: RepairScope,
+) -> ResultWithError<Vec<PathBuf>> {
+    if metadata.get("os").and_then(|os| os.as_str()) != Some("linux") {
+        return Ok(Vec::new());
+    }
+
+    let mut executables: Vec<PathBuf> =
+        match build_launch_options(app_id, install_path, metadata, &[]) {
+            Ok(launch_options) => launch_options
+                .into_iter()
+                .filter_map(|launch_option| launch_option.target_path)
+                .filter(|path| path.starts_with(install_path))
+                .collect(),
+            Err(e) => {
+                log::warn!("Can't find the executables of {}: {}", app_id, e);
+                Vec::new()
+            }
+        };
+    executables.sort();
+    executables.dedup();
+
+    let mut repaired = Vec::new();
+    for executable in &executables {
+        if repair_file(executable, true)? {
+            repaired.push(executable.clone());
+        }
+    }
+
+    let mut folders: Vec<PathBuf> = match scope {
+        RepairScope::Full => vec![install_path.to_path_buf()],
+        RepairScope::Executable => executables
+            .iter()
+            .filter_map(|exe| exe.parent())
+            .map(Path::to_path_buf)
+            .collect(),
+    };
+    folders.dedup();
+    for folder in folders {
+        let recursive = matches!(scope, RepairScope::Full);
+        repair_folder(&folder, recursive, &mut repaired)?;
+    }
+
+    for path in &repaired {
+        log::info!("Added missing execute permissions to {:?}", path);
+    }
+    Ok(repaired)
+}
+
+/// Check the whole game folder without blocking the caller, errors are only logged
+pub fn repair_permissions_in_background(
+    app_id: &str,
+    install_path: PathBuf,
+    metadata: BTreeMap<String, serde_yaml::Value>,
+) {
+    let app_id = app_id.to_string();
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) =
+            repair_exec_permissions(&app_id, &install_path, &metadata, RepairScope::Full)
+        {
+            log::error!("Failed to repair the permissions of {}: {}", app_id, e);
+        }
+    });
+}
+
+fn repair_folder(
+    folder: &Path,
+    recursive: bool,
+    repaired: &mut Vec<PathBuf>,
+) -> ResultWithError<()> {
+    for entry in fs::read_dir(folder)?.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() && recursive {
+            repair_folder(&path, recursive, repaired)?;
+        } else if file_type.is_file() && !repaired.contains(&path) {
+            match repair_file(&path, false) {
+                Ok(true) => repaired.push(path),
+                Ok(false) => {}
+                Err(e) => log::warn!("Failed to check the permissions of {:?}: {}", path, e),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the execute bits matching the read bits of an ELF file, or of a shebang
+/// script when `allow_scripts` is set. Returns true if the permissions changed.
+fn repair_file(path: &Path, allow_scripts: bool) -> ResultWithError<bool> {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Ok(false);
+    };
+    let mode = metadata.permissions().mode();
+    if !metadata.is_file() || mode & 0o111 != 0 || mode & 0o444 == 0 {
+        return Ok(false);
+    }
+
+    let mut header = [0; 4];
+    let read = fs::File::open(path)?.read(&mut header)?;
+    let header = &header[..read];
+    let is_elf = header.starts_with(ELF_MAGIC);
+    let is_script = allow_scripts && header.starts_with(SHEBANG);
+    if !is_elf && !is_script {
+        return Ok(false);
+    }
+
+    let mode = mode | ((mode & 0o444) >> 2);
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    fn create_file(path: &Path, content: &[u8], mode: u32) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    fn mode(path: &Path) -> u32 {
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn test_repair_launch_option_executables() {
+        use_test_data_dir();
+        let install_path = std::env::temp_dir().join(format!("permissions-{}", std::process::id()));
+        let binary = install_path.join("bin/game");
+        let script = install_path.join("start.sh");
+        let library = install_path.join("bin/libgame.so");
+        let data = install_path.join("data/level.pak");
+        create_file(&binary, b"\x7fELF\x02\x01\x01\x00", 0o644);
+        create_file(&script, b"#!/bin/sh\n", 0o640);
+        create_file(&library, b"\x7fELF\x02\x01\x01\x00", 0o644);
+        create_file(&data, b"\x7fELF", 0o644);
+        // Only referenced from the launch options, through a template
+        let metadata: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(
+            "os: linux
+launch_options:
+  - executable: ${INSTALL_DIR}/bin/game
+  - executable: ./start.sh",
+        )
+        .unwrap();
+
+        let repaired = repair_exec_permissions(
+            "test-permissions",
+            &install_path,
+            &metadata,
+            RepairScope::Executable,
+        )
+        .unwrap();
+        assert_eq!(repaired.len(), 3);
+        assert_eq!(mode(&binary), 0o755);
+        assert_eq!(mode(&script), 0o750);
+        assert_eq!(mode(&library), 0o755);
+        // Outside of the folders of the executables
+        assert_eq!(mode(&data), 0o644);
+
+        repair_exec_permissions(
+            "test-permissions",
+            &install_path,
+            &metadata,
+            RepairScope::Full,
+        )
+        .unwrap();
+        assert_eq!(mode(&data), 0o755);
+    }
+
+    #[test]
+    fn test_windows_games_are_left_alone() {
+        use_test_data_dir();
+        let install_path =
+            std::env::temp_dir().join(format!("permissions-windows-{}", std::process::id()));
+        let binary = install_path.join("game");
+        create_file(&binary, b"\x7fELF\x02\x01\x01\x00", 0o644);
+        let metadata: BTreeMap<String, serde_yaml::Value> =
+            serde_yaml::from_str("executable: game").unwrap();
+        let repaired =
+            repair_exec_permissions("test-windows", &install_path, &metadata, RepairScope::Full)
+                .unwrap();
+        assert!(repaired.is_empty());
+        assert_eq!(mode(&binary), 0o644);
+    }
+}
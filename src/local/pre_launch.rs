@@ -0,0 +1,164 @@
+//! Checks run by the pre-launch hook, so a game that can't start reports why
+//! instead of failing silently once launched.
+
+use crate::local::launch_options::ResolvedLaunchOption;
+use crate::local::launch_types::TargetKind;
+use crate::plugin::errors::PluginError;
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::disks::is_path_mounted;
+use crate::utils::paths::resolve_relative_path;
+use std::collections::BTreeMap;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Why a game can't be launched, reported through the `LaunchError` signal
+#[derive(Error, Debug)]
+#[error("{error}: {message}")]
+pub struct LaunchError {
+    pub error: PluginError,
+    pub message: String,
+}
+
+impl LaunchError {
+    pub fn new(error: PluginError, message: impl Into<String>) -> Self {
+        LaunchError {
+            error,
+            message: message.into(),
+        }
+    }
+}
+
+fn pre_launch_error(message: String) -> Box<dyn std::error::Error + Send + Sync> {
+    LaunchError::new(PluginError::PreLaunchError, message).into()
+}
+
+/// Check that the drive holding the game folder is mounted
+pub fn check_drive_mounted(install_path: &Path) -> EmptyResult {
+    if !is_path_mounted(install_path) {
+        return Err(pre_launch_error(format!(
+            "The drive holding {:?} is not mounted",
+            install_path
+        )));
+    }
+    Ok(())
+}
+
+/// Check that at least one launch option has an executable that exists and can be run,
+/// and that the files listed under `required_files` are present.
+pub fn validate_launch(
+    install_path: &Path,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    launch_options: &[ResolvedLaunchOption],
+) -> EmptyResult {
+    if launch_options.is_empty() {
+        return Err(pre_launch_error(
+            "No executable is set in gameinfo.yaml".to_string(),
+        ));
+    }
+
+    // The hook isn't told which option is launched, so it only fails when none of them can start
+    let problems: Vec<String> = launch_options.iter().filter_map(get_problem).collect();
+    if problems.len() == launch_options.len() {
+        return Err(pre_launch_error(problems.join(", ")));
+    }
+    for problem in problems {
+        log::warn!("A launch option can't start: {}", problem);
+    }
+
+    let required_files: Vec<String> = match metadata.get("required_files") {
+        Some(files) => serde_yaml::from_value(files.clone())
+            .map_err(|e| pre_launch_error(format!("Invalid required_files: {}", e)))?,
+        None => Vec::new(),
+    };
+    let missing: Vec<&String> = required_files
+        .iter()
+        .filter(|file| find_game_file(install_path, file).is_err())
+        .collect();
+    if !missing.is_empty() {
+        return Err(pre_launch_error(format!(
+            "Required files are missing: {}",
+            missing
+                .iter()
+                .map(|file| file.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(())
+}
+
+/// Returns why a launch option can't start, if it can't
+fn get_problem(launch_option: &ResolvedLaunchOption) -> Option<String> {
+    if let Some(problem) = &launch_option.problem {
+        return Some(problem.clone());
+    }
+    // Commands from the PATH aren't checked
+    let target_path = launch_option.target_path.as_ref()?;
+    if !target_path.is_file() {
+        return Some(format!("The executable {:?} was not found", target_path));
+    }
+    let is_native = matches!(
+        launch_option.target_kind,
+        Some(TargetKind::LinuxExecutable | TargetKind::AppImage | TargetKind::Script)
+    );
+    let mode = fs::metadata(target_path)
+        .map(|metadata| metadata.permissions().mode())
+        .unwrap_or_default();
+    if is_native && mode & 0o111 == 0 {
+        return Some(format!(
+            "The executable {:?} doesn't have execute permissions",
+            target_path
+        ));
+    }
+    None
+}
+
+/// Returns the path of a file of the game, relative paths being relative to the game folder.
+/// Paths are matched case-insensitively, like on Windows.
+fn find_game_file(install_path: &Path, path: &str) -> ResultWithError<PathBuf> {
+    if path.starts_with('/') {
+        return match Path::new(path).exists() {
+            true => Ok(PathBuf::from(path)),
+            false => Err(format!("{} was not found", path).into()),
+        };
+    }
+    Ok(install_path.join(resolve_relative_path(install_path, path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::launch_options::build_launch_options;
+    use crate::utils::system::use_test_data_dir;
+
+    #[test]
+    fn test_validate_launch() {
+        use_test_data_dir();
+        let install_path = std::env::temp_dir().join(format!("pre-launch-{}", std::process::id()));
+        fs::create_dir_all(&install_path).unwrap();
+        fs::write(install_path.join("Game.exe"), b"MZ\x90\x00").unwrap();
+        fs::write(install_path.join("start.sh"), b"echo no shebang\n").unwrap();
+        let validate = |yaml: &str| {
+            let metadata: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(yaml).unwrap();
+            let options =
+                build_launch_options("test-pre-launch", &install_path, &metadata, &[]).unwrap();
+            validate_launch(&install_path, &metadata, &options)
+        };
+
+        assert!(validate("executable: Game.exe").is_ok());
+        assert!(validate("executable: ./start.sh").is_err());
+        // Other options can still be launched
+        assert!(validate(
+            "executable: Game.exe
+launch_options:
+  - description: Play
+  - description: Script
+    executable: ./start.sh"
+        )
+        .is_ok());
+        assert!(validate("executable: Game.exe\nrequired_files: [data/game.pak]").is_err());
+        assert!(validate("name: No executable").is_err());
+    }
+}
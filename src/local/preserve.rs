@@ -0,0 +1,81 @@
+//! Backup and restore of the files listed under `preserve:` in gameinfo.yaml.
+//! Some games keep their saves and settings inside their install folder, these
+//! files are backed up when the game is uninstalled and put back when the same
+//! app is installed or imported again.
+
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::system::{copy_recursive, get_plugin_data_dir};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PRESERVED_SUBDIR: &str = "preserved";
+
+/// Returns the `preserve:` globs, relative to the game folder
+pub fn get_preserve_patterns(metadata: &BTreeMap<String, serde_yaml::Value>) -> Vec<String> {
+    metadata
+        .get("preserve")
+        .and_then(|preserve| preserve.as_sequence())
+        .map(|patterns| {
+            patterns
+                .iter()
+                .filter_map(|pattern| pattern.as_str())
+                .map(|pattern| pattern.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Copy the files matching the given patterns to the app's preserved data folder,
+/// replacing any previous backup.
+pub fn backup_preserved_files(
+    app_id: &str,
+    install_path: &Path,
+    patterns: &[String],
+) -> EmptyResult {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+    let backup_path = get_backup_path(app_id)?;
+    if backup_path.exists() {
+        fs::remove_dir_all(&backup_path)?;
+    }
+
+    let install_path_pattern = glob::Pattern::escape(&install_path.to_string_lossy());
+    for pattern in patterns {
+        let pattern = pattern.trim_start_matches("./");
+        if Path::new(pattern).is_absolute() || pattern.split('/').any(|part| part == "..") {
+            log::warn!(
+                "Ignoring preserve pattern outside of the game folder: {}",
+                pattern
+            );
+            continue;
+        }
+        for path in glob::glob(&format!("{}/{}", install_path_pattern, pattern))?.flatten() {
+            let relative_path = path.strip_prefix(install_path)?;
+            log::info!("Preserving {:?} of {}", relative_path, app_id);
+            copy_recursive(&path, &backup_path.join(relative_path))?;
+        }
+    }
+    Ok(())
+}
+
+/// Put the preserved files of an app back into its install folder, then drop the backup
+pub fn restore_preserved_files(app_id: &str, install_path: &Path) -> EmptyResult {
+    let backup_path = get_backup_path(app_id)?;
+    if !backup_path.exists() {
+        return Ok(());
+    }
+    log::info!(
+        "Restoring preserved files of {} to {:?}",
+        app_id,
+        install_path
+    );
+    copy_recursive(&backup_path, install_path)?;
+    fs::remove_dir_all(&backup_path)?;
+    Ok(())
+}
+
+fn get_backup_path(app_id: &str) -> ResultWithError<PathBuf> {
+    Ok(get_plugin_data_dir(PRESERVED_SUBDIR)?.join(app_id))
+}
@@ -0,0 +1,413 @@
+//! Save locations from a Ludusavi manifest (<https://github.com/mtkennerly/ludusavi-manifest>),
+//! used for games that don't declare `saves:` in their gameinfo.yaml.
+//! The manifest is read from `manifest.yaml` inside the plugin data folder, e.g.
+//!
+//! ```yaml
+//! Celeste:
+//!   files:
+//!     <base>/Saves:
+//!       tags: [save]
+//!       when:
+//!         - os: windows
+//!   installDir:
+//!     Celeste: {}
+//!   launch:
+//!     <base>/Celeste.exe: [...]
+//! ```
+//!
+//! Games are matched by name, then by install folder name, then by executable,
+//! ambiguous matches and generic executable names (e.g. `Game.exe`) being ignored.
+
+use crate::constants::{PLUGIN_DATA_SUBDIR, SAVE_MANIFEST_FILE};
+use crate::local::save_paths::{
+    build_cloud_paths, get_default_platform, SavePathConfig, INSTALL_DIR_PLACEHOLDER,
+};
+use crate::types::app::Platform;
+use crate::types::cloud_sync::CloudPath;
+use crate::types::results::ResultWithError;
+use parking_lot::Mutex;
+use serde::de::IgnoredAny;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+type Manifest = HashMap<String, ManifestGame>;
+
+lazy_static::lazy_static! {
+    /// Parsed manifest and the modification time of its file
+    static ref MANIFEST: Mutex<Option<(SystemTime, Arc<Manifest>)>> = Mutex::new(None);
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct ManifestGame {
+    /// Name of the game this entry is an alias of
+    alias: Option<String>,
+    files: BTreeMap<String, ManifestFile>,
+    install_dir: BTreeMap<String, IgnoredAny>,
+    launch: BTreeMap<String, IgnoredAny>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ManifestFile {
+    tags: Vec<String>,
+    when: Vec<ManifestCondition>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct ManifestCondition {
+    os: Option<String>,
+}
+
+/// Manifest placeholders and their equivalent in `saves:`
+const PLACEHOLDERS: [(&str, &str, Option<Platform>); 10] = [
+    ("<base>", INSTALL_DIR_PLACEHOLDER, None),
+    ("<winAppData>", "%APPDATA%", Some(Platform::Windows)),
+    (
+        "<winLocalAppData>",
+        "%LOCALAPPDATA%",
+        Some(Platform::Windows),
+    ),
+    (
+        "<winLocalAppDataLow>",
+        "%LOCALAPPDATALOW%",
+        Some(Platform::Windows),
+    ),
+    (
+        "<winDocuments>",
+        "%USERPROFILE%/Documents",
+        Some(Platform::Windows),
+    ),
+    ("<winPublic>", "%PUBLIC%", Some(Platform::Windows)),
+    ("<winProgramData>", "%PROGRAMDATA%", Some(Platform::Windows)),
+    ("<xdgData>", "$XDG_DATA_HOME", Some(Platform::Linux)),
+    ("<xdgConfig>", "$XDG_CONFIG_HOME", Some(Platform::Linux)),
+    ("<home>", "$HOME", None),
+];
+
+/// Executable names shared by many unrelated games, never used for matching
+const GENERIC_EXECUTABLES: [&str; 14] = [
+    "game",
+    "launcher",
+    "launch",
+    "start",
+    "play",
+    "run",
+    "setup",
+    "main",
+    "app",
+    "client",
+    "server",
+    "bin",
+    "binary",
+    "unityplayer",
+];
+
+/// Returns the save locations of a game from the manifest for the given platform.
+/// Returns an empty list when there is no manifest or the game isn't in it.
+pub fn get_manifest_save_paths(
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    install_path: &Path,
+    platform: &str,
+) -> ResultWithError<Vec<CloudPath>> {
+    let Some(manifest) = load_manifest()? else {
+        return Ok(Vec::new());
+    };
+    let Some((name, game)) = find_game(&manifest, metadata, install_path) else {
+        return Ok(Vec::new());
+    };
+    log::info!("Using the save locations of {} from the manifest", name);
+
+    let default_platform = get_default_platform(metadata);
+    let configs: Vec<SavePathConfig> = game
+        .files
+        .iter()
+        .filter(|(_, file)| file.tags.is_empty() || file.tags.iter().any(|tag| tag == "save"))
+        .filter_map(|(path, file)| to_save_path_config(path, file, &default_platform))
+        .collect();
+    Ok(build_cloud_paths(
+        configs,
+        &default_platform,
+        install_path,
+        platform,
+    ))
+}
+
+fn load_manifest() -> ResultWithError<Option<Arc<Manifest>>> {
+    let manifest_path = dirs::data_dir()
+        .ok_or("Can't get data dir")?
+        .join(PLUGIN_DATA_SUBDIR)
+        .join(SAVE_MANIFEST_FILE);
+    let Ok(modified) = fs::metadata(&manifest_path).and_then(|meta| meta.modified()) else {
+        return Ok(None);
+    };
+
+    // The full manifest is large, only parse it again when it changes
+    let mut cache = MANIFEST.lock();
+    if let Some((cached_modified, manifest)) = cache.as_ref() {
+        if *cached_modified == modified {
+            return Ok(Some(manifest.clone()));
+        }
+    }
+    log::info!("Loading the save manifest {:?}", manifest_path);
+    let manifest: Arc<Manifest> =
+        Arc::new(serde_yaml::from_reader(fs::File::open(&manifest_path)?)?);
+    *cache = Some((modified, manifest.clone()));
+    Ok(Some(manifest))
+}
+
+/// Find the game in the manifest. Each criterion only counts when it matches a
+/// single game, so the result doesn't depend on the order of the manifest.
+fn find_game<'a>(
+    manifest: &'a Manifest,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    install_path: &Path,
+) -> Option<(&'a str, &'a ManifestGame)> {
+    if let Some(name) = metadata.get("name").and_then(|name| name.as_str()) {
+        let name = normalize_name(name);
+        let matches = manifest
+            .iter()
+            .filter(|(key, _)| normalize_name(key) == name);
+        if let Some(found) = find_unique(manifest, matches, "name") {
+            return Some(found);
+        }
+    }
+
+    if let Some(folder_name) = install_path.file_name().map(|name| name.to_string_lossy()) {
+        let matches = manifest.iter().filter(|(_, game)| {
+            game.install_dir
+                .keys()
+                .any(|dir| dir.eq_ignore_ascii_case(&folder_name))
+        });
+        if let Some(found) = find_unique(manifest, matches, "folder name") {
+            return Some(found);
+        }
+    }
+
+    let executable = metadata
+        .get("executable")
+        .and_then(|exe| exe.as_str())
+        .and_then(|exe| exe.replace('\\', "/").rsplit('/').next().map(String::from))?;
+    let stem = executable
+        .rsplit_once('.')
+        .map_or(executable.as_str(), |(stem, _)| stem);
+    if GENERIC_EXECUTABLES.contains(&stem.to_lowercase().as_str()) {
+        log::debug!(
+            "Not matching the generic executable {} in the manifest",
+            executable
+        );
+        return None;
+    }
+    let matches = manifest.iter().filter(|(_, game)| {
+        game.launch.keys().any(|launch| {
+            launch
+                .rsplit('/')
+                .next()
+                .is_some_and(|name| name.eq_ignore_ascii_case(&executable))
+        })
+    });
+    find_unique(manifest, matches, "executable")
+}
+
+/// Returns the game matched, once aliases are resolved, when there is only one
+fn find_unique<'a>(
+    manifest: &'a Manifest,
+    matches: impl Iterator<Item = (&'a String, &'a ManifestGame)>,
+    criterion: &str,
+) -> Option<(&'a str, &'a ManifestGame)> {
+    let mut games: Vec<(&'a str, &'a ManifestGame)> = matches
+        .filter_map(|(name, game)| match &game.alias {
+            Some(alias) => manifest
+                .get_key_value(alias)
+                .map(|(name, game)| (name.as_str(), game)),
+            None => Some((name.as_str(), game)),
+        })
+        .collect();
+    games.sort_by_key(|(name, _)| *name);
+    games.dedup_by_key(|(name, _)| *name);
+    match games.as_slice() {
+        [] => None,
+        [game] => Some(*game),
+        _ => {
+            log::warn!(
+                "Several games of the manifest match by {}: {}",
+                criterion,
+                games
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            None
+        }
+    }
+}
+
+/// Lowercase alphanumeric characters only, so "The Witcher® 3: Wild Hunt" matches "the witcher 3 wild hunt"
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+/// Convert a manifest file entry to a save path config.
+/// Globs are split into the folder to watch and the file name pattern.
+fn to_save_path_config(
+    path: &str,
+    file: &ManifestFile,
+    default_platform: &Platform,
+) -> Option<SavePathConfig> {
+    let path = path.replace("<root>/<game>", "<base>");
+    let (placeholder, replacement, placeholder_platform) = PLACEHOLDERS
+        .iter()
+        .find(|(placeholder, _, _)| path.starts_with(placeholder))?;
+    let mut platforms: Vec<Platform> = file
+        .when
+        .iter()
+        .filter_map(|condition| match condition.os.as_deref() {
+            Some("windows") => Some(Platform::Windows),
+            Some("linux") => Some(Platform::Linux),
+            Some("mac") => Some(Platform::MacOS),
+            _ => None,
+        })
+        .collect();
+    platforms.dedup();
+    if platforms.is_empty() {
+        platforms.push(
+            placeholder_platform
+                .clone()
+                .unwrap_or_else(|| default_platform.clone()),
+        );
+    }
+    let replacement = match *placeholder {
+        "<home>" if platforms.contains(&Platform::Windows) => "%USERPROFILE%",
+        _ => replacement,
+    };
+    let path = format!("{}{}", replacement, &path[placeholder.len()..]);
+    // Paths with store or user specific placeholders can't be resolved
+    if path.contains('<') {
+        log::debug!("Skipping manifest save path {}", path);
+        return None;
+    }
+
+    let parts: Vec<&str> = path.split('/').collect();
+    let glob_index = parts.iter().position(|part| is_glob(part));
+    let (path, pattern, recursive) = match glob_index {
+        Some(index) => (
+            parts[..index].join("/"),
+            parts[parts.len() - 1].replace("**", "*"),
+            index < parts.len() - 1 || parts[index].contains("**"),
+        ),
+        // A file name rather than a folder
+        None if parts.len() > 1 && looks_like_file(parts[parts.len() - 1]) => (
+            parts[..parts.len() - 1].join("/"),
+            parts[parts.len() - 1].to_string(),
+            false,
+        ),
+        None => (path, "*".to_string(), true),
+    };
+    Some(SavePathConfig {
+        alias: None,
+        path,
+        pattern,
+        recursive,
+        platforms: Some(platforms),
+    })
+}
+
+fn is_glob(part: &str) -> bool {
+    part.contains(['*', '?', '['])
+}
+
+fn looks_like_file(name: &str) -> bool {
+    PathBuf::from(name).extension().is_some_and(|ext| {
+        ext.len() <= 4 && ext.to_string_lossy().chars().all(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+Celeste:
+  installDir:
+    Celeste: {}
+  launch:
+    <base>/Celeste.exe: [{}]
+Celeste Classic:
+  alias: Celeste
+Doom:
+  launch:
+    <base>/Game.exe: [{}]
+    <base>/doom.exe: [{}]
+DOOM:
+  launch:
+    <base>/doomx64.exe: [{}]
+Quake:
+  installDir:
+    id Software: {}
+  launch:
+    <base>/Game.exe: [{}]
+Hexen:
+  installDir:
+    id Software: {}
+  launch:
+    <base>/hexen.exe: [{}]
+Heretic:
+  launch:
+    <base>/hexen.exe: [{}]
+"#;
+
+    fn find(name: Option<&str>, folder: &str, executable: &str) -> Option<String> {
+        let manifest: Manifest = serde_yaml::from_str(MANIFEST).unwrap();
+        let mut metadata = BTreeMap::from([("executable".to_string(), executable.into())]);
+        if let Some(name) = name {
+            metadata.insert("name".to_string(), name.into());
+        }
+        find_game(&manifest, &metadata, &Path::new("/games").join(folder))
+            .map(|(name, _)| name.to_string())
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        assert_eq!(
+            find(Some("celeste"), "x", "x.exe").as_deref(),
+            Some("Celeste")
+        );
+        // Aliases resolve to the game they point to
+        assert_eq!(
+            find(Some("Celeste: Classic"), "x", "x.exe").as_deref(),
+            Some("Celeste")
+        );
+    }
+
+    #[test]
+    fn test_find_by_folder_and_executable() {
+        assert_eq!(find(None, "CELESTE", "x.exe").as_deref(), Some("Celeste"));
+        assert_eq!(
+            find(None, "x", "bin\\Celeste.EXE").as_deref(),
+            Some("Celeste")
+        );
+    }
+
+    #[test]
+    fn test_ambiguous_matches_are_ignored() {
+        // "Doom" and "DOOM" have the same normalized name, "doom.exe" is unique
+        assert_eq!(find(Some("doom"), "x", "doom.exe").as_deref(), Some("Doom"));
+        // Two games share the folder name and the executable
+        assert_eq!(find(None, "id Software", "hexen.exe"), None);
+    }
+
+    #[test]
+    fn test_generic_executables_are_ignored() {
+        assert_eq!(find(None, "x", "Game.exe"), None);
+        assert_eq!(find(None, "x", "launcher"), None);
+    }
+}
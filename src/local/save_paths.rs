@@ -0,0 +1,379 @@
+//! Save locations declared under `saves:` in gameinfo.yaml, e.g.
+//!
+//! ```yaml
+//! saves:
+//!   - alias: saves
+//!     path: "%APPDATA%/MyGame"
+//!     pattern: "*.sav"
+//!     recursive: false
+//!     platforms: [windows]
+//! ```
+//!
+//! Paths can start with a Windows placeholder (`%APPDATA%`, `%LOCALAPPDATA%`,
+//! `%USERPROFILE%`, ...), which is kept as is since it points inside the Wine
+//! prefix, or with a Linux placeholder (`$HOME`, `$XDG_DATA_HOME`, ...) or
+//! `<install_dir>`, which are expanded to absolute paths.
+
+use crate::types::app::Platform;
+use crate::types::cloud_sync::CloudPath;
+use crate::types::results::ResultWithError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+pub const INSTALL_DIR_PLACEHOLDER: &str = "<install_dir>";
+
+const WINDOWS_PLACEHOLDERS: [&str; 7] = [
+    "%APPDATA%",
+    "%LOCALAPPDATA%",
+    "%LOCALAPPDATALOW%",
+    "%USERPROFILE%",
+    "%PUBLIC%",
+    "%PROGRAMDATA%",
+    "%SAVEDGAMES%",
+];
+
+/// Name of the user inside Proton prefixes
+const WINE_USER: &str = "steamuser";
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SavePathConfig {
+    #[serde(default)]
+    pub alias: Option<String>,
+    pub path: String,
+    #[serde(default = "default_pattern")]
+    pub pattern: String,
+    #[serde(default = "crate::types::default_true")]
+    pub recursive: bool,
+    /// Defaults to the `os` of the game
+    #[serde(default)]
+    pub platforms: Option<Vec<Platform>>,
+}
+
+fn default_pattern() -> String {
+    "*".to_string()
+}
+
+/// Returns the save locations of a game for the given platform, an empty platform matches all.
+pub fn get_save_paths(
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    install_path: &Path,
+    platform: &str,
+) -> ResultWithError<Vec<CloudPath>> {
+    let Some(saves) = metadata.get("saves") else {
+        return Ok(Vec::new());
+    };
+    let configs: Vec<SavePathConfig> = serde_yaml::from_value(saves.clone())?;
+    Ok(build_cloud_paths(
+        configs,
+        &get_default_platform(metadata),
+        install_path,
+        platform,
+    ))
+}
+
+/// Platform of the game, from its `os`
+pub fn get_default_platform(metadata: &BTreeMap<String, serde_yaml::Value>) -> Platform {
+    match metadata.get("os").and_then(|os| os.as_str()) {
+        Some("linux") => Platform::Linux,
+        _ => Platform::Windows,
+    }
+}
+
+/// Turn save path configs into cloud paths for the given platform, an empty platform matches all.
+pub fn build_cloud_paths(
+    configs: Vec<SavePathConfig>,
+    default_platform: &Platform,
+    install_path: &Path,
+    platform: &str,
+) -> Vec<CloudPath> {
+    let mut cloud_paths = Vec::new();
+    for (index, config) in configs.into_iter().enumerate() {
+        let platforms = config
+            .platforms
+            .clone()
+            .unwrap_or_else(|| vec![default_platform.clone()]);
+        if !platform.is_empty()
+            && !platforms
+                .iter()
+                .any(|p| p.to_string().eq_ignore_ascii_case(platform))
+        {
+            continue;
+        }
+        let path = match expand_save_path(&config.path, install_path) {
+            Ok(path) => path,
+            Err(e) => {
+                log::warn!("Ignoring save path {}: {}", config.path, e);
+                continue;
+            }
+        };
+        cloud_paths.push(CloudPath {
+            alias: config
+                .alias
+                .unwrap_or_else(|| format!("saves{}", index + 1)),
+            path,
+            pattern: config.pattern,
+            recursive: config.recursive,
+            platforms: platforms
+                .iter()
+                .map(|p| p.to_string().to_lowercase())
+                .collect(),
+        });
+    }
+    cloud_paths
+}
+
+/// Resolve the save locations of a game to paths on this machine. Windows placeholders
+/// are resolved inside the Wine prefix set with `wine_prefix` in gameinfo.yaml, and
+/// skipped when there is none. Fails when every location is inside an unknown prefix.
+pub fn resolve_local_save_paths(
+    save_paths: Vec<CloudPath>,
+    metadata: &BTreeMap<String, serde_yaml::Value>,
+    install_path: &Path,
+) -> ResultWithError<Vec<CloudPath>> {
+    let wine_prefix = match metadata.get("wine_prefix").and_then(|p| p.as_str()) {
+        Some(prefix) => Some(PathBuf::from(expand_save_path(prefix, install_path)?)),
+        None => None,
+    };
+    let count = save_paths.len();
+    let mut local_paths = Vec::new();
+    for mut save_path in save_paths {
+        if save_path.path.starts_with('%') {
+            let Some(wine_prefix) = &wine_prefix else {
+                log::debug!(
+                    "Skipping save path inside an unknown Wine prefix: {}",
+                    save_path.path
+                );
+                continue;
+            };
+            save_path.path = resolve_windows_save_path(&save_path.path, wine_prefix)?;
+        }
+        local_paths.push(save_path);
+    }
+    if count > 0 && local_paths.is_empty() {
+        return Err(
+            "The saves are inside the Wine prefix, which is unsupported \
+                    unless wine_prefix is set in gameinfo.yaml"
+                .into(),
+        );
+    }
+    Ok(local_paths)
+}
+
+/// Resolve a save path starting with a Windows placeholder inside a Wine prefix,
+/// e.g. `%APPDATA%/MyGame` becomes `<prefix>/drive_c/users/steamuser/AppData/Roaming/MyGame`
+pub fn resolve_windows_save_path(path: &str, wine_prefix: &Path) -> ResultWithError<String> {
+    let (placeholder, rest) = path.split_once('/').unwrap_or((path, ""));
+    let user_dir = format!("drive_c/users/{}", WINE_USER);
+    let folder = match placeholder.to_uppercase().as_str() {
+        "%USERPROFILE%" => user_dir,
+        "%APPDATA%" => format!("{}/AppData/Roaming", user_dir),
+        "%LOCALAPPDATA%" => format!("{}/AppData/Local", user_dir),
+        "%LOCALAPPDATALOW%" => format!("{}/AppData/LocalLow", user_dir),
+        "%SAVEDGAMES%" => format!("{}/Saved Games", user_dir),
+        "%PUBLIC%" => "drive_c/users/Public".to_string(),
+        "%PROGRAMDATA%" => "drive_c/ProgramData".to_string(),
+        _ => return Err(format!("Unknown placeholder {}", placeholder).into()),
+    };
+    Ok(join_path(&wine_prefix.join(folder).to_string_lossy(), rest))
+}
+
+/// Expand the placeholder at the start of a save path.
+/// Windows placeholders are only normalized, everything else becomes an absolute path.
+pub fn expand_save_path(path: &str, install_path: &Path) -> ResultWithError<String> {
+    let path = path.replace('\\', "/");
+    let (prefix, rest) = match path.split_once('/') {
+        Some((prefix, rest)) => (prefix, rest),
+        None => (path.as_str(), ""),
+    };
+
+    if prefix.starts_with('%') {
+        let placeholder = prefix.to_uppercase();
+        if !WINDOWS_PLACEHOLDERS.contains(&placeholder.as_str()) {
+            return Err(format!("Unknown placeholder {}", prefix).into());
+        }
+        return Ok(join_path(&placeholder, rest));
+    }
+
+    let base: PathBuf = match prefix {
+        INSTALL_DIR_PLACEHOLDER => install_path.to_path_buf(),
+        "~" | "$HOME" | "${HOME}" => dirs::home_dir().ok_or("Can't get home dir")?,
+        "$XDG_DATA_HOME" | "${XDG_DATA_HOME}" => dirs::data_dir().ok_or("Can't get data dir")?,
+        "$XDG_CONFIG_HOME" | "${XDG_CONFIG_HOME}" => {
+            dirs::config_dir().ok_or("Can't get config dir")?
+        }
+        "$XDG_STATE_HOME" | "${XDG_STATE_HOME}" => {
+            dirs::state_dir().ok_or("Can't get state dir")?
+        }
+        "$XDG_CACHE_HOME" | "${XDG_CACHE_HOME}" => {
+            dirs::cache_dir().ok_or("Can't get cache dir")?
+        }
+        _ if prefix.starts_with('$') || prefix.starts_with('<') => {
+            return Err(format!("Unknown placeholder {}", prefix).into());
+        }
+        // Paths without placeholder are relative to the game folder
+        _ if !path.starts_with('/') => {
+            return Ok(join_path(&install_path.to_string_lossy(), &path))
+        }
+        _ => return Ok(path),
+    };
+    Ok(join_path(&base.to_string_lossy(), rest))
+}
+
+fn join_path(base: &str, rest: &str) -> String {
+    let rest = rest.trim_matches('/');
+    if rest.is_empty() {
+        base.to_string()
+    } else {
+        format!("{}/{}", base.trim_end_matches('/'), rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    fn cloud_path(path: &str) -> CloudPath {
+        CloudPath {
+            alias: "saves".to_string(),
+            path: path.to_string(),
+            pattern: "*".to_string(),
+            recursive: true,
+            platforms: vec![],
+        }
+    }
+
+    #[test]
+    fn test_expand_save_path() {
+        use_test_data_dir();
+        let install_path = Path::new("/games/my-game");
+        let home = dirs::home_dir().unwrap();
+        let home = home.to_string_lossy();
+        assert_eq!(
+            expand_save_path("<install_dir>/saves/", install_path).unwrap(),
+            "/games/my-game/saves"
+        );
+        assert_eq!(
+            expand_save_path("<install_dir>", install_path).unwrap(),
+            "/games/my-game"
+        );
+        assert_eq!(
+            expand_save_path("$HOME/.mygame", install_path).unwrap(),
+            format!("{}/.mygame", home)
+        );
+        assert_eq!(
+            expand_save_path("~/.mygame", install_path).unwrap(),
+            format!("{}/.mygame", home)
+        );
+        assert_eq!(
+            expand_save_path("${XDG_DATA_HOME}/MyGame", install_path).unwrap(),
+            format!("{}/MyGame", dirs::data_dir().unwrap().to_string_lossy())
+        );
+        assert_eq!(
+            expand_save_path("%appdata%\\MyGame\\Saves", install_path).unwrap(),
+            "%APPDATA%/MyGame/Saves"
+        );
+        assert_eq!(
+            expand_save_path("saves\\slot1", install_path).unwrap(),
+            "/games/my-game/saves/slot1"
+        );
+        assert_eq!(
+            expand_save_path("/mnt/saves", install_path).unwrap(),
+            "/mnt/saves"
+        );
+        for unknown in [
+            "$FOO/saves",
+            "${FOO}/saves",
+            "%FOO%/saves",
+            "<game_dir>/saves",
+        ] {
+            assert!(
+                expand_save_path(unknown, install_path).is_err(),
+                "{}",
+                unknown
+            );
+        }
+    }
+
+    #[test]
+    fn test_get_save_paths() {
+        let install_path = Path::new("/games/my-game");
+        let metadata: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(
+            "saves:
+  - path: \"%APPDATA%/MyGame\"
+  - alias: config
+    path: <install_dir>/config
+    pattern: \"*.ini\"
+    recursive: false
+    platforms: [linux, windows]
+  - path: $HOME/.mygame
+    platforms: [linux]
+  - path: $FOO/ignored",
+        )
+        .unwrap();
+
+        let paths = get_save_paths(&metadata, install_path, "windows").unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[0].alias, "saves1");
+        assert_eq!(paths[0].path, "%APPDATA%/MyGame");
+        assert_eq!(paths[0].pattern, "*");
+        assert!(paths[0].recursive);
+        assert_eq!(paths[0].platforms, vec!["windows"]);
+        assert_eq!(paths[1].alias, "config");
+        assert_eq!(paths[1].path, "/games/my-game/config");
+        assert_eq!(paths[1].pattern, "*.ini");
+        assert!(!paths[1].recursive);
+
+        let aliases = |platform: &str| -> Vec<String> {
+            get_save_paths(&metadata, install_path, platform)
+                .unwrap()
+                .into_iter()
+                .map(|path| path.alias)
+                .collect()
+        };
+        assert_eq!(aliases("Linux"), vec!["config", "saves3"]);
+        assert_eq!(aliases(""), vec!["saves1", "config", "saves3"]);
+        assert!(get_save_paths(&BTreeMap::new(), install_path, "")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_resolve_windows_save_path() {
+        let prefix = Path::new("/prefix");
+        assert_eq!(
+            resolve_windows_save_path("%APPDATA%/MyGame", prefix).unwrap(),
+            "/prefix/drive_c/users/steamuser/AppData/Roaming/MyGame"
+        );
+        assert_eq!(
+            resolve_windows_save_path("%PROGRAMDATA%", prefix).unwrap(),
+            "/prefix/drive_c/ProgramData"
+        );
+        assert!(resolve_windows_save_path("%WINDIR%/MyGame", prefix).is_err());
+    }
+
+    #[test]
+    fn test_resolve_local_save_paths() {
+        let install_path = Path::new("/games/my-game");
+        let save_paths = || vec![cloud_path("%APPDATA%/MyGame"), cloud_path("/saves")];
+
+        let metadata = BTreeMap::new();
+        let paths = resolve_local_save_paths(save_paths(), &metadata, install_path).unwrap();
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].path, "/saves");
+        assert!(resolve_local_save_paths(
+            vec![cloud_path("%APPDATA%/MyGame")],
+            &metadata,
+            install_path
+        )
+        .is_err());
+
+        let metadata = BTreeMap::from([("wine_prefix".to_string(), "prefix".into())]);
+        let paths = resolve_local_save_paths(save_paths(), &metadata, install_path).unwrap();
+        assert_eq!(
+            paths[0].path,
+            "/games/my-game/prefix/drive_c/users/steamuser/AppData/Roaming/MyGame"
+        );
+    }
+}
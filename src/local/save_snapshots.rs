@@ -0,0 +1,418 @@
+//! Local snapshots of the save files of a game, taken around each game session.
+//! File contents are stored once per app in a content addressed object store, so
+//! snapshots only cost the space of the files that changed since the previous one.
+//!
+//! Layout, inside the plugin data folder:
+//!
+//! ```text
+//! snapshots/<app_id>/objects/<sha256>
+//! snapshots/<app_id>/manifests/<snapshot_id>.json
+//! ```
+
+use crate::constants::SAVE_SNAPSHOT_RETENTION;
+use crate::types::app::SaveSnapshot;
+use crate::types::cloud_sync::CloudPath;
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::system::get_plugin_data_dir;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SNAPSHOTS_SUBDIR: &str = "snapshots";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct SnapshotFile {
+    /// Absolute path of the file when the snapshot was taken
+    path: PathBuf,
+    hash: String,
+    size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SnapshotManifest {
+    id: String,
+    created_at: i64,
+    reason: String,
+    files: Vec<SnapshotFile>,
+}
+
+impl From<&SnapshotManifest> for SaveSnapshot {
+    fn from(manifest: &SnapshotManifest) -> Self {
+        SaveSnapshot {
+            id: manifest.id.clone(),
+            created_at: manifest.created_at,
+            reason: manifest.reason.clone(),
+            file_count: manifest.files.len() as u32,
+            total_size: manifest.files.iter().map(|file| file.size).sum(),
+        }
+    }
+}
+
+/// Snapshot the save files of an app. Nothing is stored when the files didn't
+/// change since the latest snapshot. Returns the id of the new snapshot, if any.
+pub fn create_snapshot(
+    app_id: &str,
+    save_paths: &[CloudPath],
+    reason: &str,
+) -> ResultWithError<Option<String>> {
+    take_snapshot(app_id, save_paths, reason, None)
+}
+
+/// Snapshot the save files of an app, never expiring the snapshot `keep`
+fn take_snapshot(
+    app_id: &str,
+    save_paths: &[CloudPath],
+    reason: &str,
+    keep: Option<&str>,
+) -> ResultWithError<Option<String>> {
+    let app_dir = get_app_dir(app_id)?;
+    let objects_dir = app_dir.join("objects");
+    fs::create_dir_all(&objects_dir)?;
+
+    let mut files = Vec::new();
+    for save_path in save_paths {
+        for path in list_save_files(save_path)? {
+            let hash = store_object(&objects_dir, &path)?;
+            let size = fs::metadata(&path)?.len();
+            files.push(SnapshotFile { path, hash, size });
+        }
+    }
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut manifests = read_manifests(&app_dir)?;
+    if manifests.last().is_some_and(|latest| latest.files == files) {
+        log::info!("Saves of {} didn't change, skipping snapshot", app_id);
+        return Ok(None);
+    }
+    if files.is_empty() && manifests.is_empty() {
+        return Ok(None);
+    }
+
+    let now = Utc::now();
+    let manifest = SnapshotManifest {
+        id: now.format("%Y%m%d%H%M%S%3f").to_string(),
+        created_at: now.timestamp(),
+        reason: reason.to_string(),
+        files,
+    };
+    let manifests_dir = app_dir.join("manifests");
+    fs::create_dir_all(&manifests_dir)?;
+    fs::write(
+        manifests_dir.join(format!("{}.json", manifest.id)),
+        serde_json::to_string(&manifest)?,
+    )?;
+    log::info!(
+        "Created save snapshot {} of {} ({} files)",
+        manifest.id,
+        app_id,
+        manifest.files.len()
+    );
+    let id = manifest.id.clone();
+    manifests.push(manifest);
+
+    apply_retention(&app_dir, manifests, keep)?;
+    Ok(Some(id))
+}
+
+/// List the snapshots of an app, oldest first
+pub fn list_snapshots(app_id: &str) -> ResultWithError<Vec<SaveSnapshot>> {
+    Ok(read_manifests(&get_app_dir(app_id)?)?
+        .iter()
+        .map(SaveSnapshot::from)
+        .collect())
+}
+
+/// Write the files of a snapshot back to where they were taken from.
+/// The current files are snapshotted first, so a wrong restore can be undone.
+/// Files created since the snapshot are left alone.
+pub fn restore_snapshot(app_id: &str, snapshot_id: &str, save_paths: &[CloudPath]) -> EmptyResult {
+    let app_dir = get_app_dir(app_id)?;
+    let manifest = read_manifests(&app_dir)?
+        .into_iter()
+        .find(|manifest| manifest.id == snapshot_id)
+        .ok_or(format!(
+            "Snapshot {} of {} doesn't exist",
+            snapshot_id, app_id
+        ))?;
+    take_snapshot(app_id, save_paths, "pre_restore", Some(snapshot_id))?;
+
+    log::info!("Restoring save snapshot {} of {}", snapshot_id, app_id);
+    for file in &manifest.files {
+        if let Some(parent) = file.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(app_dir.join("objects").join(&file.hash), &file.path)?;
+    }
+    Ok(())
+}
+
+/// Returns the files of a save location that exist on this machine.
+/// Windows placeholders must be resolved with `resolve_local_save_paths` first.
+pub fn list_save_files(save_path: &CloudPath) -> ResultWithError<Vec<PathBuf>> {
+    if save_path.path.starts_with('%') {
+        return Err(format!("Unresolved save path {}", save_path.path).into());
+    }
+    let pattern = glob::Pattern::new(&save_path.pattern)?;
+    let mut files = Vec::new();
+    collect_files(
+        Path::new(&save_path.path),
+        &pattern,
+        save_path.recursive,
+        &mut files,
+    )?;
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    pattern: &glob::Pattern,
+    recursive: bool,
+    files: &mut Vec<PathBuf>,
+) -> EmptyResult {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                collect_files(&path, pattern, recursive, files)?;
+            }
+        } else if path
+            .file_name()
+            .is_some_and(|name| pattern.matches(&name.to_string_lossy()))
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Copy a file into the object store, named after the hash of its contents
+fn store_object(objects_dir: &Path, path: &Path) -> ResultWithError<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut fs::File::open(path)?, &mut hasher)?;
+    let hash = format!("{:x}", hasher.finalize());
+
+    let object_path = objects_dir.join(&hash);
+    if !object_path.exists() {
+        let tmp_path = objects_dir.join(format!("{}.tmp", hash));
+        fs::copy(path, &tmp_path)?;
+        fs::rename(&tmp_path, &object_path)?;
+    }
+    Ok(hash)
+}
+
+/// Only keep the latest snapshots and drop the objects no snapshot refers to anymore.
+/// The snapshot `keep`, about to be restored, is kept in any case.
+fn apply_retention(
+    app_dir: &Path,
+    manifests: Vec<SnapshotManifest>,
+    keep: Option<&str>,
+) -> EmptyResult {
+    if manifests.len() <= SAVE_SNAPSHOT_RETENTION {
+        return Ok(());
+    }
+    let expired_count = manifests.len() - SAVE_SNAPSHOT_RETENTION;
+    let (expired, manifests): (Vec<_>, Vec<_>) =
+        manifests
+            .into_iter()
+            .enumerate()
+            .partition(|(index, manifest)| {
+                *index < expired_count && Some(manifest.id.as_str()) != keep
+            });
+    let expired: Vec<_> = expired.into_iter().map(|(_, manifest)| manifest).collect();
+    let manifests: Vec<_> = manifests
+        .into_iter()
+        .map(|(_, manifest)| manifest)
+        .collect();
+    for manifest in &expired {
+        log::info!("Removing expired save snapshot {}", manifest.id);
+        fs::remove_file(
+            app_dir
+                .join("manifests")
+                .join(format!("{}.json", manifest.id)),
+        )?;
+    }
+
+    let referenced: HashSet<&str> = manifests
+        .iter()
+        .flat_map(|manifest| manifest.files.iter().map(|file| file.hash.as_str()))
+        .collect();
+    for entry in fs::read_dir(app_dir.join("objects"))? {
+        let path = entry?.path();
+        if path
+            .file_name()
+            .is_some_and(|name| !referenced.contains(name.to_string_lossy().as_ref()))
+        {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the manifests of an app, oldest first
+fn read_manifests(app_dir: &Path) -> ResultWithError<Vec<SnapshotManifest>> {
+    let mut manifests = Vec::new();
+    let entries = match fs::read_dir(app_dir.join("manifests")) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(manifests),
+        Err(e) => return Err(e.into()),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(manifest) => manifests.push(manifest),
+            Err(e) => log::warn!("Ignoring unreadable save snapshot {:?}: {}", path, e),
+        }
+    }
+    manifests.sort_by(|a: &SnapshotManifest, b| a.id.cmp(&b.id));
+    Ok(manifests)
+}
+
+/// Returns the snapshot folder of an app. App ids come from D-Bus callers, so
+/// ids that would point outside of the snapshots folder are rejected.
+fn get_app_dir(app_id: &str) -> ResultWithError<PathBuf> {
+    if app_id.is_empty() || app_id.contains(['/', '\\']) || app_id.contains("..") {
+        return Err(format!("Invalid app id {}", app_id).into());
+    }
+    Ok(get_plugin_data_dir(SNAPSHOTS_SUBDIR)?.join(app_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn create_save_dir(name: &str) -> (PathBuf, Vec<CloudPath>) {
+        use_test_data_dir();
+        let dir =
+            std::env::temp_dir().join(format!("playtron-saves-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("slots")).unwrap();
+        let save_paths = vec![CloudPath {
+            alias: "saves".to_string(),
+            path: dir.to_string_lossy().to_string(),
+            pattern: "*.sav".to_string(),
+            recursive: true,
+            platforms: vec![],
+        }];
+        (dir, save_paths)
+    }
+
+    /// Snapshot ids have a millisecond resolution
+    fn create(app_id: &str, save_paths: &[CloudPath]) -> Option<String> {
+        sleep(Duration::from_millis(2));
+        create_snapshot(app_id, save_paths, "test").unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let app_id = "snapshot-restore";
+        let (dir, save_paths) = create_save_dir(app_id);
+        fs::write(dir.join("slots/1.sav"), "first").unwrap();
+        fs::write(dir.join("slots/2.sav"), "first").unwrap();
+        fs::write(dir.join("notes.txt"), "ignored").unwrap();
+
+        let first = create(app_id, &save_paths).unwrap();
+        assert_eq!(create(app_id, &save_paths), None);
+        let snapshots = list_snapshots(app_id).unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].file_count, 2);
+        // Identical files are stored once
+        let objects = fs::read_dir(get_app_dir(app_id).unwrap().join("objects")).unwrap();
+        assert_eq!(objects.count(), 1);
+
+        fs::write(dir.join("slots/1.sav"), "second").unwrap();
+        create(app_id, &save_paths).unwrap();
+        restore_snapshot(app_id, &first, &save_paths).unwrap();
+        assert_eq!(
+            fs::read_to_string(dir.join("slots/1.sav")).unwrap(),
+            "first"
+        );
+        assert!(restore_snapshot(app_id, "missing", &save_paths).is_err());
+    }
+
+    #[test]
+    fn test_restore_snapshots_current_saves() {
+        let app_id = "snapshot-pre-restore";
+        let (dir, save_paths) = create_save_dir(app_id);
+        fs::write(dir.join("slot.sav"), "first").unwrap();
+        let first = create(app_id, &save_paths).unwrap();
+        sleep(Duration::from_millis(2));
+        fs::write(dir.join("slot.sav"), "unsaved").unwrap();
+
+        restore_snapshot(app_id, &first, &save_paths).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("slot.sav")).unwrap(), "first");
+        let snapshots = list_snapshots(app_id).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[1].reason, "pre_restore");
+
+        // The wrong restore can be undone
+        sleep(Duration::from_millis(2));
+        restore_snapshot(app_id, &snapshots[1].id, &save_paths).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("slot.sav")).unwrap(), "unsaved");
+    }
+
+    #[test]
+    fn test_restore_keeps_the_oldest_snapshot() {
+        let app_id = "snapshot-restore-oldest";
+        let (dir, save_paths) = create_save_dir(app_id);
+        for index in 0..SAVE_SNAPSHOT_RETENTION {
+            fs::write(dir.join("slot.sav"), index.to_string()).unwrap();
+            create(app_id, &save_paths).unwrap();
+        }
+        fs::write(dir.join("slot.sav"), "unsaved").unwrap();
+        let oldest = list_snapshots(app_id).unwrap()[0].id.clone();
+        sleep(Duration::from_millis(2));
+        restore_snapshot(app_id, &oldest, &save_paths).unwrap();
+        assert_eq!(fs::read_to_string(dir.join("slot.sav")).unwrap(), "0");
+    }
+
+    #[test]
+    fn test_invalid_app_ids() {
+        use_test_data_dir();
+        for app_id in ["", "../other", "a/b", "..", "a\\b"] {
+            assert!(list_snapshots(app_id).is_err(), "{}", app_id);
+            assert!(restore_snapshot(app_id, "1", &[]).is_err(), "{}", app_id);
+        }
+    }
+
+    #[test]
+    fn test_retention() {
+        let app_id = "snapshot-retention";
+        let (dir, save_paths) = create_save_dir(app_id);
+        for index in 0..SAVE_SNAPSHOT_RETENTION + 3 {
+            fs::write(dir.join("slot.sav"), index.to_string()).unwrap();
+            create(app_id, &save_paths).unwrap();
+        }
+        let snapshots = list_snapshots(app_id).unwrap();
+        assert_eq!(snapshots.len(), SAVE_SNAPSHOT_RETENTION);
+        // Objects of the expired snapshots are removed
+        let objects = fs::read_dir(get_app_dir(app_id).unwrap().join("objects")).unwrap();
+        assert_eq!(objects.count(), SAVE_SNAPSHOT_RETENTION);
+    }
+
+    #[test]
+    fn test_unresolved_save_path() {
+        let save_path = CloudPath {
+            alias: "saves".to_string(),
+            path: "%APPDATA%/MyGame".to_string(),
+            pattern: "*".to_string(),
+            recursive: true,
+            platforms: vec![],
+        };
+        assert!(list_save_files(&save_path).is_err());
+    }
+}
@@ -1,33 +1,46 @@
 use crate::constants::LIBRARY_PROVIDER_ID;
+use crate::plugin::errors::PluginError;
 use crate::plugin::library_provider::LibraryProviderSignals;
 use crate::types::app::{
-    self, EulaEntry, InstalledApp, ItemMetadata, LaunchOption, PlaytronImage, PlaytronProvider,
-    ProviderItem, ReleaseState,
+    EulaEntry, InstalledApp, ItemMetadata, LaunchOption, MoveStatus, PlaySession, Playtime,
+    PlaytronImage, PlaytronProvider, ProviderItem, ReleaseState, SaveSnapshot, TrashedApp,
 };
-use crate::types::cloud_sync::CloudPath;
-use crate::types::results::ResultWithError;
+use crate::types::cloud_sync::{CloudPath, CloudSyncParams};
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::hardware::get_device_hardware_tags;
 use crate::utils::system::{get_folder_name, move_folder_with_progress};
 use futures::future;
 use futures_util::StreamExt;
-use parking_lot::Mutex;
 use rsa::pkcs1::EncodeRsaPublicKey;
 use rsa::pkcs8::LineEnding;
 use rsa::{RsaPrivateKey, RsaPublicKey};
 use std::collections::BTreeMap;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::vec;
-use tokio_util::sync::CancellationToken;
+use tokio::fs;
+use tokio::sync::mpsc::UnboundedSender;
 use zbus::fdo;
 use zbus::object_server::SignalEmitter;
 
+use super::cloud_sync::{sync_saves, SyncFailure};
 use super::connector::LocalConnector;
+use super::launch_options::{build_launch_options, ResolvedLaunchOption};
+use super::launch_scripts::{run_launch_script, LaunchStage};
+use super::move_jobs::{cancel_move_job, get_move_status, is_moving, start_move_job, MoveJobGuard};
+use super::move_journal::{
+    list_interrupted_moves, read_journal, MoveJournal, MoveJournalEntry, MoveStage, Recovery,
+};
+use super::permissions::{repair_exec_permissions, repair_permissions_in_background, RepairScope};
+use super::pre_launch::{check_drive_mounted, validate_launch, LaunchError};
+use super::preserve::restore_preserved_files;
+use super::save_manifest::get_manifest_save_paths;
+use super::save_paths::{get_save_paths, resolve_local_save_paths};
+use super::save_snapshots::{create_snapshot, list_snapshots, restore_snapshot};
+use super::sessions::{end_session, get_play_sessions, get_playtime, is_running, start_session};
+use super::trash::{list_trashed_apps, purge_trash, restore_trashed_app};
 
 pub const DEFAULT_RELEASE_DATE: u64 = 0;
 
-lazy_static::lazy_static! {
-    static ref MOVE_CANCELLATION_TOKEN: Mutex<Option<CancellationToken>> = Mutex::default();
-}
-
 #[derive(Clone)]
 pub struct LocalService {
     rsa: RsaPrivateKey,
@@ -162,40 +175,235 @@ impl LocalService {
         &self,
         app_id: String,
         using_offline_mode: bool,
-    ) -> fdo::Result<Vec<String>> {
+    ) -> ResultWithError<Vec<String>> {
         log::info!(
             "pre launch hook for app_id {} (offline mode: {})",
             &app_id,
             using_offline_mode
         );
 
+        let install_path = self.connector.find_app(&app_id)?.ok_or_else(|| {
+            LaunchError::new(
+                PluginError::PreLaunchError,
+                format!("{} is not installed", app_id),
+            )
+        })?;
+        let metadata = self
+            .connector
+            .load_metadata(&app_id)
+            .await
+            .map_err(|e| LaunchError::new(PluginError::PreLaunchError, e.to_string()))?;
+        let requires_network = metadata
+            .get("requires_network")
+            .and_then(|r| r.as_bool())
+            .unwrap_or(false);
+        if using_offline_mode && requires_network {
+            log::error!(
+                "Can't launch {} in offline mode, it requires network",
+                app_id
+            );
+            return Err(LaunchError::new(
+                PluginError::NetworkRequired,
+                format!("{} requires a network connection", app_id),
+            )
+            .into());
+        }
+        if let Err(e) =
+            repair_exec_permissions(&app_id, &install_path, &metadata, RepairScope::Executable)
+        {
+            log::warn!("Failed to repair the permissions of {}: {}", app_id, e);
+        }
+        if let Err(e) = self.check_launch(&app_id, &install_path, &metadata).await {
+            log::error!("Can't launch {}: {}", app_id, e);
+            return Err(e);
+        }
+        if let Err(e) =
+            run_launch_script(&app_id, &install_path, &metadata, LaunchStage::PreLaunch).await
+        {
+            log::error!("Can't launch {}: {}", app_id, e);
+            return Err(LaunchError::new(PluginError::PreLaunchError, e.to_string()).into());
+        }
+
+        self.snapshot_saves(&app_id, "pre_launch").await;
+        // Only started once every step passed, a failed pre-launch never leaves a session behind
+        start_session(&app_id, &install_path);
         Ok(vec![])
     }
 
+    /// Check that the game can be started, failures are `LaunchError`s
+    async fn check_launch(
+        &self,
+        app_id: &str,
+        install_path: &Path,
+        metadata: &BTreeMap<String, serde_yaml::Value>,
+    ) -> EmptyResult {
+        check_drive_mounted(install_path)?;
+        let launch_options = self
+            .resolve_launch_options(app_id)
+            .await
+            .map_err(|e| LaunchError::new(PluginError::PreLaunchError, e.to_string()))?;
+        validate_launch(install_path, metadata, &launch_options)
+    }
+
+    pub async fn post_launch_hook(&self, app_id: &str) -> fdo::Result<()> {
+        log::info!("post launch hook for {}", app_id);
+        end_session(app_id);
+        if let Ok(Some(install_path)) = self.connector.find_app(app_id) {
+            let metadata = self
+                .connector
+                .load_metadata(app_id)
+                .await
+                .unwrap_or_default();
+            if let Err(e) =
+                run_launch_script(app_id, &install_path, &metadata, LaunchStage::PostLaunch).await
+            {
+                log::error!("Post-launch cleanup of {} failed: {}", app_id, e);
+            }
+        }
+        self.snapshot_saves(app_id, "post_launch").await;
+        Ok(())
+    }
+
+    /// Snapshot the declared save files of an app. Failures are only logged, a
+    /// missing snapshot should never prevent playing.
+    async fn snapshot_saves(&self, app_id: &str, reason: &str) {
+        let save_paths = match self.get_local_save_paths(app_id).await {
+            Ok(save_paths) if save_paths.is_empty() => return,
+            Ok(save_paths) => save_paths,
+            Err(e) => {
+                log::warn!("Can't snapshot the saves of {}: {}", app_id, e);
+                return;
+            }
+        };
+        let snapshot_app_id = app_id.to_string();
+        let reason = reason.to_string();
+        match tokio::task::spawn_blocking(move || {
+            create_snapshot(&snapshot_app_id, &save_paths, &reason)
+        })
+        .await
+        {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => log::error!("Failed to snapshot the saves of {}: {}", app_id, e),
+            Err(e) => log::error!("Failed to snapshot the saves of {}: {}", app_id, e),
+        }
+    }
+
+    /// Returns the snapshots of an app. Fails when none were taken because its
+    /// saves can't be reached, rather than returning an empty list.
+    pub async fn get_save_snapshots(&self, app_id: &str) -> ResultWithError<Vec<SaveSnapshot>> {
+        let snapshots = list_snapshots(app_id)?;
+        if snapshots.is_empty() {
+            self.get_local_save_paths(app_id).await?;
+        }
+        Ok(snapshots)
+    }
+
+    /// Returns the save locations of an app as paths on this machine
+    async fn get_local_save_paths(&self, app_id: &str) -> ResultWithError<Vec<CloudPath>> {
+        let save_paths = self.get_save_path_patterns(app_id, "").await?;
+        let install_path = self
+            .connector
+            .find_app(app_id)?
+            .ok_or(format!("{} is not installed", app_id))?;
+        let metadata = self.connector.load_metadata(app_id).await?;
+        resolve_local_save_paths(save_paths, &metadata, &install_path)
+            .map_err(|e| format!("Can't reach the saves of {}: {}", app_id, e).into())
+    }
+
+    pub fn get_hardware_tags(&self) -> Vec<String> {
+        get_device_hardware_tags()
+    }
+
+    pub fn get_playtime(&self, app_id: &str) -> ResultWithError<Playtime> {
+        get_playtime(app_id)
+    }
+
+    pub fn get_play_sessions(&self, app_id: &str) -> ResultWithError<Vec<PlaySession>> {
+        get_play_sessions(app_id)
+    }
+
+    pub async fn restore_save_snapshot(&self, app_id: &str, snapshot_id: &str) -> EmptyResult {
+        if is_running(app_id) {
+            return Err(PluginError::AppInUse.into());
+        }
+        let save_paths = self.get_local_save_paths(app_id).await?;
+        let app_id = app_id.to_string();
+        let snapshot_id = snapshot_id.to_string();
+        tokio::task::spawn_blocking(move || restore_snapshot(&app_id, &snapshot_id, &save_paths))
+            .await?
+    }
+
+    /// Upload or download the save files of an app to the configured sync target,
+    /// sending the progress, from 0.0 to 1.0, to the given channel
+    pub async fn sync_saves(
+        &self,
+        params: CloudSyncParams,
+        progress: UnboundedSender<f64>,
+    ) -> Result<(), SyncFailure> {
+        let app_id = params.provider_app_id.clone();
+        let operation = params.operation;
+        if is_running(&app_id) {
+            return Err(SyncFailure::Error(
+                PluginError::AppInUse,
+                format!("{} is running, can't sync its saves", app_id),
+            ));
+        }
+        let save_paths = self
+            .get_local_save_paths(&app_id)
+            .await
+            .map_err(|e| SyncFailure::Error(PluginError::MissingDirectory, e.to_string()))?;
+        tokio::task::spawn_blocking(move || {
+            sync_saves(
+                &params.provider_app_id,
+                &save_paths,
+                params.operation,
+                params.conflict_resolution,
+                &|fraction| {
+                    let _ = progress.send(fraction);
+                },
+            )
+        })
+        .await
+        .map_err(|e| SyncFailure::transfer(operation, e))?
+    }
+
     pub async fn get_launch_options(&self, app_id: &str) -> ResultWithError<Vec<LaunchOption>> {
         log::info!("get launch options for {}", app_id);
+        Ok(self
+            .resolve_launch_options(app_id)
+            .await?
+            .into_iter()
+            .map(|option| option.launch_option)
+            .collect())
+    }
+
+    async fn resolve_launch_options(
+        &self,
+        app_id: &str,
+    ) -> ResultWithError<Vec<ResolvedLaunchOption>> {
         let metadata = self.connector.load_metadata(app_id).await?;
-        if let Some(executable) = metadata.get("executable").and_then(|exe| exe.as_str()) {
-            Ok(vec![LaunchOption {
-                description: "Launch".to_string(),
-                executable: executable.to_owned(),
-                working_directory: "".to_string(),
-                environment: vec![],
-                arguments: "".to_string(),
-                hardware_tags: vec![],
-                launch_type: app::LaunchType::Game,
-            }])
-        } else {
-            Ok(vec![])
-        }
+        let install_path = self
+            .connector
+            .find_app(app_id)?
+            .ok_or(format!("Couldn't find install path for {}", app_id))?;
+        let save_paths = self
+            .get_save_path_patterns(app_id, "")
+            .await
+            .unwrap_or_default();
+        build_launch_options(app_id, &install_path, &metadata, &save_paths)
     }
-    pub async fn cancel_move_item(&self) -> fdo::Result<()> {
-        if let Some(token) = MOVE_CANCELLATION_TOKEN.lock().take() {
-            token.cancel();
+
+    pub async fn cancel_move_item(&self, app_id: &str) -> fdo::Result<()> {
+        if !cancel_move_job(app_id) {
+            log::warn!("No move in progress for {}", app_id);
         }
         Ok(())
     }
 
+    pub fn get_move_status(&self, app_id: &str) -> MoveStatus {
+        get_move_status(app_id)
+    }
+
     pub async fn move_item(
         &self,
         app_id: String,
@@ -203,81 +411,289 @@ impl LocalService {
         emitter: SignalEmitter<'_>,
     ) -> ResultWithError<String> {
         log::info!("Move {} to {}", app_id, base_path);
-        if MOVE_CANCELLATION_TOKEN.lock().is_some() {
-            return Err("An app move operation is already in progress".into());
-        }
         let from_path = self.connector.find_app(&app_id)?.ok_or("App not found")?;
         let folder_name = get_folder_name(from_path.clone())
             .ok_or("Failed to get folder name from source path")?;
-        let path_buf = Path::new(&base_path).join(folder_name);
+        let path_buf = self
+            .connector
+            .resolve_library_root(&base_path)?
+            .join(folder_name);
+        if path_buf == from_path {
+            return Err(format!("{} is already in {}", app_id, base_path).into());
+        }
         let dest_path = path_buf.to_str().ok_or("Invalid destination path")?;
         log::info!("Moving from {:?} to {:?}", from_path, dest_path);
-        let from = from_path
-            .as_os_str()
-            .to_str()
-            .ok_or("Invalid source path")?;
-        let dest_clone = dest_path.to_string();
-        let from_clone = from.to_string();
 
-        let cancel_token = CancellationToken::new();
-        *MOVE_CANCELLATION_TOKEN.lock() = Some(cancel_token.clone());
+        let job = start_move_job(&app_id, &from_path, &path_buf)?;
+        Self::finish_rollback(&app_id).await?;
+        let journal = MoveJournal::create(&app_id, from_path.clone(), path_buf.clone())?;
 
         let emitter = emitter.into_owned();
-        tokio::spawn(async move {
-            let mut progress =
-                move_folder_with_progress(&from_clone, &dest_clone, cancel_token).await;
-            while let Some(progress_result) = progress.next().await {
-                match progress_result {
-                    Ok(progress_result) => match progress_result {
-                        Ok(progress) => {
-                            LibraryProviderSignals::move_item_progressed(
-                                &emitter.clone(),
-                                app_id.clone(),
-                                progress.into(),
-                            )
-                            .await?;
-                        }
-                        Err(e) => {
-                            LibraryProviderSignals::move_item_failed(
-                                &emitter.clone(),
+        tokio::spawn(Self::run_move(
+            app_id,
+            from_path,
+            path_buf.clone(),
+            job,
+            journal,
+            emitter,
+        ));
+        Ok(dest_path.to_string())
+    }
+
+    async fn run_move(
+        app_id: String,
+        from_path: PathBuf,
+        dest_path: PathBuf,
+        job: MoveJobGuard,
+        journal: MoveJournal,
+        emitter: SignalEmitter<'static>,
+    ) -> EmptyResult {
+        let from = from_path.to_str().ok_or("Invalid source path")?;
+        let dest = dest_path.to_str().ok_or("Invalid destination path")?;
+
+        let mut copied_journal = journal.clone();
+        let mut progress = move_folder_with_progress(from, dest, job.cancel_token(), move || {
+            copied_journal.set_stage(MoveStage::RemovingSource)
+        })
+        .await;
+        while let Some(progress_result) = progress.next().await {
+            match progress_result {
+                Ok(progress_result) => match progress_result {
+                    Ok(progress) => {
+                        job.set_progress(progress.fraction());
+                        LibraryProviderSignals::move_item_progressed(
+                            &emitter,
+                            app_id.clone(),
+                            progress.percent.into(),
+                        )
+                        .await?;
+                        if progress.total_bytes > 0 {
+                            LibraryProviderSignals::move_item_transfer_progressed(
+                                &emitter,
                                 app_id.clone(),
-                                e.to_string(),
+                                progress.moved_bytes,
+                                progress.total_bytes,
+                                progress.bytes_per_second,
+                                progress.eta_seconds,
                             )
                             .await?;
-                            return Err(e);
                         }
-                    },
-                    Err(e) => {
-                        LibraryProviderSignals::move_item_failed(
-                            &emitter.clone(),
-                            app_id.clone(),
-                            e.to_string(),
-                        )
-                        .await?;
-                        return Err(e);
                     }
-                }
+                    Err(e) => return Self::fail_move(app_id, e, job, journal, &emitter).await,
+                },
+                Err(e) => return Self::fail_move(app_id, e, job, journal, &emitter).await,
             }
+        }
 
-            LibraryProviderSignals::move_item_completed(
-                &emitter.clone(),
-                app_id.clone(),
-                dest_clone,
-            )
+        // Release the job before notifying so the app can be moved again right away
+        drop(job);
+        journal.remove();
+        LibraryProviderSignals::move_item_completed(&emitter, app_id.clone(), dest.to_string())
             .await?;
-            Ok(())
-        });
-        Ok(dest_path.to_string())
+        Ok(())
+    }
+
+    async fn fail_move(
+        app_id: String,
+        error: Box<dyn std::error::Error + Send + Sync>,
+        job: MoveJobGuard,
+        mut journal: MoveJournal,
+        emitter: &SignalEmitter<'static>,
+    ) -> EmptyResult {
+        let error: Box<dyn std::error::Error + Send + Sync> = if job.is_disk_removed() {
+            log::error!(
+                "Move of {} failed after a disk was removed: {}",
+                app_id,
+                error
+            );
+            PluginError::DiskNotFound.into()
+        } else {
+            error
+        };
+        if job.is_destination_removed() {
+            // The partial copy can't be removed until the disk comes back
+            if let Err(e) = journal.set_stage(MoveStage::RollingBack) {
+                log::error!("Failed to update the move journal of {}: {}", app_id, e);
+            }
+        } else {
+            journal.remove();
+        }
+        drop(job);
+        LibraryProviderSignals::move_item_failed(emitter, app_id, error.to_string()).await?;
+        Err(error)
+    }
+
+    /// Remove the partial copy left by an earlier failed move of the app, whose
+    /// disk was unplugged at the time, before starting a new move
+    async fn finish_rollback(app_id: &str) -> EmptyResult {
+        let Some(entry) = read_journal(app_id)? else {
+            return Ok(());
+        };
+        if entry.stage != MoveStage::RollingBack {
+            return Err(format!("A previous move of {} has not been recovered yet", app_id).into());
+        }
+        if !entry
+            .destination
+            .parent()
+            .is_some_and(|parent| parent.exists())
+        {
+            return Err(format!(
+                "The partial copy of a previous move of {} is on a disk that is not mounted",
+                app_id
+            )
+            .into());
+        }
+        if entry.destination.exists() {
+            log::info!("Removing partial copy {:?}", entry.destination);
+            fs::remove_dir_all(&entry.destination).await?;
+        }
+        MoveJournal::open(entry)?.remove();
+        Ok(())
+    }
+
+    /// Resume or roll back the moves that were interrupted by a crash, a power
+    /// loss or a restart of the plugin, and report their outcome.
+    /// Returns true when some moves must be recovered later, once their disk is mounted.
+    pub async fn recover_interrupted_moves(&self, emitter: SignalEmitter<'static>) -> bool {
+        let entries = match list_interrupted_moves() {
+            Ok(entries) => entries,
+            Err(e) => {
+                log::error!("Failed to read the move journal: {}", e);
+                return false;
+            }
+        };
+        // Resumed moves keep their journal until they are done
+        for entry in entries
+            .into_iter()
+            .filter(|entry| !is_moving(&entry.app_id))
+        {
+            if let Err(e) = self.recover_move(entry, emitter.clone()).await {
+                log::error!("Failed to recover interrupted move: {}", e);
+            }
+        }
+        list_interrupted_moves()
+            .is_ok_and(|entries| entries.iter().any(|entry| !is_moving(&entry.app_id)))
+    }
+
+    async fn recover_move(
+        &self,
+        entry: MoveJournalEntry,
+        emitter: SignalEmitter<'static>,
+    ) -> EmptyResult {
+        let app_id = entry.app_id.clone();
+        let source = entry.source.clone();
+        let destination = entry.destination.clone();
+        let destination_available = destination.parent().is_some_and(|parent| parent.exists());
+        let recovery = entry.plan_recovery(entry.is_source_mounted(), destination_available);
+        log::info!(
+            "Recovering interrupted move of {} from {:?} to {:?} ({:?}): {:?}",
+            app_id,
+            source,
+            destination,
+            entry.stage,
+            recovery
+        );
+        let mut journal = MoveJournal::open(entry)?;
+
+        match recovery {
+            Recovery::Wait => {}
+            // The failure was already reported, only the partial copy is left to clean up
+            Recovery::RemovePartialCopy => {
+                if destination.exists() {
+                    log::info!("Removing partial copy {:?}", destination);
+                    fs::remove_dir_all(&destination).await?;
+                }
+                journal.remove();
+            }
+            Recovery::Complete => {
+                if source.exists() {
+                    log::info!("Finishing removal of {:?}", source);
+                    fs::remove_dir_all(&source).await?;
+                }
+                journal.remove();
+                LibraryProviderSignals::move_item_completed(
+                    &emitter,
+                    app_id,
+                    destination.to_string_lossy().to_string(),
+                )
+                .await?;
+            }
+            // rsync only transfers what is missing from the destination
+            Recovery::Resume => {
+                log::info!("Resuming move of {}", app_id);
+                let job = start_move_job(&app_id, &source, &destination)?;
+                tokio::spawn(Self::run_move(
+                    app_id,
+                    source,
+                    destination,
+                    job,
+                    journal,
+                    emitter,
+                ));
+            }
+            Recovery::RollBack => {
+                if destination_available {
+                    if destination.exists() {
+                        log::info!("Removing partial copy {:?}", destination);
+                        fs::remove_dir_all(&destination).await?;
+                    }
+                    journal.remove();
+                } else {
+                    // The partial copy is removed once its disk is back
+                    journal.set_stage(MoveStage::RollingBack)?;
+                }
+                LibraryProviderSignals::move_item_failed(
+                    &emitter,
+                    app_id,
+                    "The move was interrupted and has been rolled back".to_string(),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Local games are copied manually, so installing only makes sure the library
+    /// root exists on the requested disk.
+    pub async fn install(&self, app_id: &str, dest_path: &str) -> EmptyResult {
+        log::info!("Install {} to {}", app_id, dest_path);
+        let library_root = self.connector.resolve_library_root(dest_path)?;
+        log::info!("Library root for {} is {:?}", app_id, library_root);
+        Ok(())
     }
 
     pub async fn uninstall(&self, app_id: &str) -> fdo::Result<()> {
         log::info!("Uninstall {}", app_id);
         match self.connector.uninstall(app_id).await {
-            Ok(_) => Ok(()),
+            Ok(_) => {
+                self.purge_trash();
+                Ok(())
+            }
             Err(e) => Err(fdo::Error::Failed(format!("{}", e))),
         }
     }
 
+    pub async fn get_trashed_apps(&self) -> ResultWithError<Vec<TrashedApp>> {
+        let library_paths = self.connector.get_library_paths()?;
+        // Measuring the trashed folders walks whole game trees
+        Ok(tokio::task::spawn_blocking(move || list_trashed_apps(&library_paths)).await?)
+    }
+
+    pub fn restore_trashed_app(&self, app_id: &str) -> ResultWithError<String> {
+        let path = restore_trashed_app(&self.connector.get_library_paths()?, app_id)?;
+        Ok(path.to_string_lossy().to_string())
+    }
+
+    /// Purge the trash in the background, removing whole game folders can take a while
+    pub fn purge_trash(&self) {
+        match self.connector.get_library_paths() {
+            Ok(library_paths) => {
+                tokio::task::spawn_blocking(move || purge_trash(&library_paths));
+            }
+            Err(e) => log::error!("Failed to get library paths to purge the trash: {}", e),
+        }
+    }
+
     pub async fn get_save_path_patterns(
         &self,
         app_id: &str,
@@ -288,11 +704,46 @@ impl LocalService {
             app_id,
             platform
         );
-        Ok(Vec::new())
+        let install_path = self
+            .connector
+            .find_app(app_id)
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?
+            .ok_or(fdo::Error::Failed(format!("{} is not installed", app_id)))?;
+        let metadata = self
+            .connector
+            .load_metadata(app_id)
+            .await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+        if metadata.contains_key("saves") {
+            return get_save_paths(&metadata, &install_path, platform)
+                .map_err(|e| fdo::Error::Failed(format!("Invalid saves for {}: {}", app_id, e)));
+        }
+
+        let platform = platform.to_string();
+        tokio::task::spawn_blocking(move || {
+            get_manifest_save_paths(&metadata, &install_path, &platform)
+        })
+        .await
+        .map_err(|e| fdo::Error::Failed(e.to_string()))?
+        .map_err(|e| fdo::Error::Failed(format!("Failed to read the save manifest: {}", e)))
     }
 
     pub async fn import(&self, app_id: &str, install_folder: &str) -> fdo::Result<()> {
         log::info!("Import {} from {}", app_id, install_folder);
+        let install_path = match Path::new(install_folder).is_dir() {
+            true => Some(PathBuf::from(install_folder)),
+            false => self
+                .connector
+                .find_app(app_id)
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?,
+        };
+        if let Some(install_path) = install_path {
+            restore_preserved_files(app_id, &install_path)
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            if let Ok(metadata) = self.connector.load_metadata(app_id).await {
+                repair_permissions_in_background(app_id, install_path, metadata);
+            }
+        }
         Ok(())
     }
 }
@@ -0,0 +1,291 @@
+//! Tracks the apps currently being played, between the pre-launch and the
+//! post-launch hooks, and records the playtime of each session.
+
+use crate::types::app::{PlaySession, Playtime};
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::system::get_plugin_data_dir;
+use chrono::Utc;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System, UpdateKind};
+
+const PLAYTIME_SUBDIR: &str = "playtime";
+
+/// Time given to a game to start after the pre-launch hook, before its session
+/// can expire for lack of a running process
+const SESSION_STARTUP_GRACE: Duration = Duration::from_secs(120);
+
+struct RunningApp {
+    install_path: PathBuf,
+    started_at: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref RUNNING_APPS: Mutex<HashMap<String, RunningApp>> = Mutex::default();
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct PlaytimeRecord {
+    /// Start of the session in progress, kept on disk so a session survives a plugin restart
+    current_session: Option<i64>,
+    sessions: Vec<PlaySession>,
+}
+
+pub fn start_session(app_id: &str, install_path: &Path) {
+    RUNNING_APPS.lock().insert(
+        app_id.to_string(),
+        RunningApp {
+            install_path: install_path.to_path_buf(),
+            started_at: Instant::now(),
+        },
+    );
+    let result = update_record(app_id, |record| {
+        if let Some(started_at) = record.current_session {
+            log::warn!(
+                "The previous session of {} started at {} never ended",
+                app_id,
+                started_at
+            );
+        }
+        record.current_session = Some(Utc::now().timestamp());
+    });
+    if let Err(e) = result {
+        log::error!("Failed to record the session start of {}: {}", app_id, e);
+    }
+}
+
+pub fn end_session(app_id: &str) {
+    RUNNING_APPS.lock().remove(app_id);
+    let result = update_record(app_id, |record| {
+        let Some(started_at) = record.current_session.take() else {
+            log::warn!("No session of {} in progress", app_id);
+            return;
+        };
+        let ended_at = Utc::now().timestamp().max(started_at);
+        log::info!(
+            "{} was played for {} seconds",
+            app_id,
+            ended_at - started_at
+        );
+        record.sessions.push(PlaySession {
+            started_at,
+            ended_at,
+        });
+    });
+    if let Err(e) = result {
+        log::error!("Failed to record the session end of {}: {}", app_id, e);
+    }
+}
+
+/// Returns true while the app is played. The post-launch hook never comes when
+/// the game crashes or playserve restarts, so a session also expires once no
+/// process runs from the game folder anymore.
+pub fn is_running(app_id: &str) -> bool {
+    check_running(app_id, has_process_in)
+}
+
+/// Expires the session of the app once the grace period is over and
+/// `has_process` finds nothing running from its folder
+fn check_running(app_id: &str, has_process: impl Fn(&Path) -> bool) -> bool {
+    let install_path = match RUNNING_APPS.lock().get(app_id) {
+        None => return false,
+        Some(app) if app.started_at.elapsed() < SESSION_STARTUP_GRACE => return true,
+        Some(app) => app.install_path.clone(),
+    };
+    if has_process(&install_path) {
+        return true;
+    }
+    log::warn!(
+        "No process of {} is running anymore, expiring its session",
+        app_id
+    );
+    RUNNING_APPS.lock().remove(app_id);
+    // The end of the session is unknown, it isn't counted in the playtime
+    let result = update_record(app_id, |record| record.current_session = None);
+    if let Err(e) = result {
+        log::error!("Failed to expire the session of {}: {}", app_id, e);
+    }
+    false
+}
+
+/// Returns true when a process was started from, or runs with its working
+/// directory in, the given folder
+fn has_process_in(install_path: &Path) -> bool {
+    let install_path = install_path
+        .canonicalize()
+        .unwrap_or_else(|_| install_path.to_path_buf());
+    let install_path_str = install_path.to_string_lossy().to_string();
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_exe(UpdateKind::Always)
+            .with_cwd(UpdateKind::Always)
+            .with_cmd(UpdateKind::Always),
+    );
+    system.processes().values().any(|process| {
+        process
+            .exe()
+            .is_some_and(|exe| exe.starts_with(&install_path))
+            || process
+                .cwd()
+                .is_some_and(|cwd| cwd.starts_with(&install_path))
+            || process
+                .cmd()
+                .iter()
+                .any(|arg| arg.to_string_lossy().contains(&install_path_str))
+    })
+}
+
+/// Returns the total playtime of an app, the session in progress included
+pub fn get_playtime(app_id: &str) -> ResultWithError<Playtime> {
+    let record = read_record(app_id)?;
+    let now = Utc::now().timestamp();
+    let current = record
+        .current_session
+        .filter(|_| is_running(app_id))
+        .map(|started_at| (now - started_at).max(0) as u64);
+    let total_seconds = record
+        .sessions
+        .iter()
+        .map(|session| (session.ended_at - session.started_at) as u64)
+        .sum::<u64>()
+        + current.unwrap_or_default();
+    let last_played = record
+        .current_session
+        .into_iter()
+        .chain(record.sessions.iter().map(|session| session.started_at))
+        .max()
+        .unwrap_or_default();
+    Ok(Playtime {
+        app_id: app_id.to_string(),
+        total_seconds,
+        last_played,
+        session_count: record.sessions.len() as u32 + u32::from(current.is_some()),
+    })
+}
+
+/// Returns the finished sessions of an app, oldest first
+pub fn get_play_sessions(app_id: &str) -> ResultWithError<Vec<PlaySession>> {
+    Ok(read_record(app_id)?.sessions)
+}
+
+fn get_record_path(app_id: &str) -> ResultWithError<PathBuf> {
+    Ok(get_plugin_data_dir(PLAYTIME_SUBDIR)?.join(format!("{}.json", app_id)))
+}
+
+fn read_record(app_id: &str) -> ResultWithError<PlaytimeRecord> {
+    let path = get_record_path(app_id)?;
+    if !path.exists() {
+        return Ok(PlaytimeRecord::default());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn update_record(app_id: &str, update: impl FnOnce(&mut PlaytimeRecord)) -> EmptyResult {
+    let mut record = read_record(app_id)?;
+    update(&mut record);
+    let path = get_record_path(app_id)?;
+    let tmp_path = path.with_extension("json.tmp");
+    fs::write(&tmp_path, serde_json::to_string(&record)?)?;
+    fs::rename(tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::system::use_test_data_dir;
+
+    fn start_expired_session(app_id: &str) {
+        start_session(app_id, Path::new("/nonexistent/game"));
+        RUNNING_APPS.lock().get_mut(app_id).unwrap().started_at =
+            Instant::now() - SESSION_STARTUP_GRACE - Duration::from_secs(1);
+    }
+
+    #[test]
+    fn test_playtime_record() {
+        use_test_data_dir();
+        let app_id = "test-sessions-playtime";
+        update_record(app_id, |record| {
+            record.sessions = vec![
+                PlaySession {
+                    started_at: 1000,
+                    ended_at: 1600,
+                },
+                PlaySession {
+                    started_at: 5000,
+                    ended_at: 5030,
+                },
+            ]
+        })
+        .unwrap();
+
+        let sessions = get_play_sessions(app_id).unwrap();
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[1].started_at, 5000);
+        let playtime = get_playtime(app_id).unwrap();
+        assert_eq!(playtime.total_seconds, 630);
+        assert_eq!(playtime.last_played, 5000);
+        assert_eq!(playtime.session_count, 2);
+
+        start_session(app_id, Path::new("/nonexistent/game"));
+        assert!(is_running(app_id));
+        assert_eq!(get_playtime(app_id).unwrap().session_count, 3);
+        end_session(app_id);
+        assert!(!is_running(app_id));
+        let sessions = get_play_sessions(app_id).unwrap();
+        assert_eq!(sessions.len(), 3);
+        assert!(sessions[2].ended_at >= sessions[2].started_at);
+        assert!(read_record(app_id).unwrap().current_session.is_none());
+    }
+
+    #[test]
+    fn test_unknown_app_has_no_playtime() {
+        use_test_data_dir();
+        let playtime = get_playtime("test-sessions-unknown").unwrap();
+        assert_eq!(playtime.total_seconds, 0);
+        assert_eq!(playtime.session_count, 0);
+        assert!(get_play_sessions("test-sessions-unknown")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_session_in_startup_grace() {
+        use_test_data_dir();
+        let app_id = "test-sessions-grace";
+        start_session(app_id, Path::new("/nonexistent/game"));
+        assert!(check_running(app_id, |_| false));
+        end_session(app_id);
+    }
+
+    #[test]
+    fn test_session_kept_while_process_runs() {
+        use_test_data_dir();
+        let app_id = "test-sessions-process";
+        start_expired_session(app_id);
+        assert!(check_running(app_id, |path| path == Path::new("/nonexistent/game")));
+        assert!(read_record(app_id).unwrap().current_session.is_some());
+        end_session(app_id);
+    }
+
+    #[test]
+    fn test_session_expires_without_process() {
+        use_test_data_dir();
+        let app_id = "test-sessions-expired";
+        start_expired_session(app_id);
+        assert!(!check_running(app_id, |_| false));
+        assert!(!RUNNING_APPS.lock().contains_key(app_id));
+        let record = read_record(app_id).unwrap();
+        assert!(record.current_session.is_none());
+        // The expired session isn't counted
+        assert!(record.sessions.is_empty());
+        assert_eq!(get_playtime(app_id).unwrap().session_count, 0);
+    }
+}
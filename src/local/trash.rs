@@ -0,0 +1,316 @@
+//! Per-disk trash for uninstalled apps. Uninstalled app folders are moved to a
+//! `.trash` folder inside their library root so they can be restored, and are
+//! purged automatically once they get too old or the disk runs low on space.
+
+use crate::constants::{TRASH_DIR, TRASH_MAX_AGE_DAYS, TRASH_MIN_FREE_SPACE_PERCENT};
+use crate::types::app::TrashedApp;
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::disks::get_disk_space;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize)]
+struct TrashInfo {
+    app_id: String,
+    original_path: PathBuf,
+    /// Unix timestamp in seconds
+    trashed_at: i64,
+    /// Name of the folder in the trash, unique so a new copy never replaces the
+    /// previous one before it is safely in the trash. Defaults to the app id.
+    #[serde(default)]
+    folder: Option<String>,
+}
+
+struct TrashEntry {
+    info: TrashInfo,
+    path: PathBuf,
+    info_path: PathBuf,
+}
+
+impl TrashEntry {
+    fn purge(&self) -> EmptyResult {
+        log::info!("Purging {:?} from the trash", self.path);
+        if self.path.is_symlink() || self.path.is_file() {
+            fs::remove_file(&self.path)?;
+        } else if self.path.exists() {
+            fs::remove_dir_all(&self.path)?;
+        }
+        fs::remove_file(&self.info_path)?;
+        Ok(())
+    }
+}
+
+/// Returns true for the trash folder of a library root
+pub fn is_trash_dir(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == TRASH_DIR)
+}
+
+/// Move an app folder to the trash of its library root
+pub fn move_to_trash(library_root: &Path, app_id: &str, install_path: &Path) -> EmptyResult {
+    let trash_dir = library_root.join(TRASH_DIR);
+    fs::create_dir_all(&trash_dir)?;
+    let previous_entries: Vec<TrashEntry> = read_trash(&trash_dir)
+        .into_iter()
+        .filter(|entry| entry.info.app_id == app_id)
+        .collect();
+
+    let trashed_at = Utc::now();
+    let folder = (0..)
+        .map(|index| match index {
+            0 => format!("{}-{}", app_id, trashed_at.timestamp_millis()),
+            _ => format!("{}-{}-{}", app_id, trashed_at.timestamp_millis(), index),
+        })
+        .find(|folder| {
+            !trash_dir.join(folder).exists() && !trash_dir.join(format!("{}.json", folder)).exists()
+        })
+        .ok_or("No free name in the trash")?;
+    let info = TrashInfo {
+        app_id: app_id.to_string(),
+        original_path: install_path.to_path_buf(),
+        trashed_at: trashed_at.timestamp(),
+        folder: Some(folder.clone()),
+    };
+    let info_path = trash_dir.join(format!("{}.json", folder));
+    fs::write(&info_path, serde_json::to_string(&info)?)?;
+    log::info!("Moving {:?} to the trash", install_path);
+    if let Err(e) = fs::rename(install_path, trash_dir.join(&folder)) {
+        let _ = fs::remove_file(&info_path);
+        return Err(format!("Failed to move {:?} to the trash: {}", install_path, e).into());
+    }
+
+    // Only keep the latest uninstalled copy of an app, once it is in the trash
+    for previous in previous_entries {
+        if let Err(e) = previous.purge() {
+            log::error!("Failed to purge {:?}: {}", previous.path, e);
+        }
+    }
+    Ok(())
+}
+
+/// List the trashed apps of all the given library roots
+pub fn list_trashed_apps(library_roots: &[PathBuf]) -> Vec<TrashedApp> {
+    library_roots
+        .iter()
+        .flat_map(|root| read_trash(&root.join(TRASH_DIR)))
+        .map(|entry| TrashedApp {
+            disk_size: fs_extra::dir::get_size(&entry.path).unwrap_or_default(),
+            app_id: entry.info.app_id,
+            original_path: entry.info.original_path.to_string_lossy().to_string(),
+            trashed_at: entry.info.trashed_at,
+        })
+        .collect()
+}
+
+/// Move a trashed app back to where it was uninstalled from
+pub fn restore_trashed_app(library_roots: &[PathBuf], app_id: &str) -> ResultWithError<PathBuf> {
+    let entry = library_roots
+        .iter()
+        .flat_map(|root| read_trash(&root.join(TRASH_DIR)))
+        .filter(|entry| entry.info.app_id == app_id)
+        .max_by_key(|entry| entry.info.trashed_at)
+        .ok_or(format!("{} is not in the trash", app_id))?;
+    let original_path = &entry.info.original_path;
+    if original_path.exists() || original_path.is_symlink() {
+        return Err(format!(
+            "{:?} already exists, can't restore {}",
+            original_path, app_id
+        )
+        .into());
+    }
+    log::info!("Restoring {} to {:?}", app_id, original_path);
+    fs::rename(&entry.path, original_path)?;
+    fs::remove_file(&entry.info_path)?;
+    Ok(original_path.clone())
+}
+
+/// Purge the trashed apps older than the maximum age, then the oldest ones
+/// until the disk of each library root has enough free space again.
+pub fn purge_trash(library_roots: &[PathBuf]) {
+    let now = Utc::now().timestamp();
+    for library_root in library_roots {
+        purge_library_trash(library_root, now, get_disk_space);
+    }
+}
+
+fn purge_library_trash(
+    library_root: &Path,
+    now: i64,
+    get_disk_space: impl Fn(&Path) -> Option<(u64, u64)>,
+) {
+    let max_age = TRASH_MAX_AGE_DAYS * 24 * 60 * 60;
+    let mut entries = read_trash(&library_root.join(TRASH_DIR));
+    entries.sort_by_key(|entry| entry.info.trashed_at);
+
+    let mut remaining = Vec::new();
+    for entry in entries {
+        if now - entry.info.trashed_at > max_age {
+            if let Err(e) = entry.purge() {
+                log::error!("Failed to purge {:?}: {}", entry.path, e);
+            }
+        } else {
+            remaining.push(entry);
+        }
+    }
+
+    let Some((mut free_space, total_space)) = get_disk_space(library_root) else {
+        return;
+    };
+    let min_free_space = total_space / 100 * TRASH_MIN_FREE_SPACE_PERCENT;
+    for entry in remaining {
+        if free_space >= min_free_space {
+            break;
+        }
+        let size = fs_extra::dir::get_size(&entry.path).unwrap_or_default();
+        match entry.purge() {
+            Ok(_) => free_space += size,
+            Err(e) => log::error!("Failed to purge {:?}: {}", entry.path, e),
+        }
+    }
+}
+
+fn read_trash(trash_dir: &Path) -> Vec<TrashEntry> {
+    let Ok(dir_entries) = fs::read_dir(trash_dir) else {
+        return Vec::new();
+    };
+    let mut entries = Vec::new();
+    for dir_entry in dir_entries.flatten() {
+        let info_path = dir_entry.path();
+        if info_path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let info: TrashInfo = match fs::read_to_string(&info_path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| serde_json::from_str(&data).map_err(|e| e.to_string()))
+        {
+            Ok(info) => info,
+            Err(e) => {
+                log::warn!("Ignoring unreadable trash entry {:?}: {}", info_path, e);
+                continue;
+            }
+        };
+        entries.push(TrashEntry {
+            path: trash_dir.join(info.folder.as_ref().unwrap_or(&info.app_id)),
+            info,
+            info_path,
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_library(name: &str, apps: &[&str]) -> PathBuf {
+        let library_root =
+            std::env::temp_dir().join(format!("trash-{}-{}", name, std::process::id()));
+        for app_id in apps {
+            fs::create_dir_all(library_root.join(app_id)).unwrap();
+            fs::write(library_root.join(app_id).join("game.exe"), vec![0; 100]).unwrap();
+        }
+        library_root
+    }
+
+    fn set_trashed_at(library_root: &Path, app_id: &str, trashed_at: i64) {
+        let entry = read_trash(&library_root.join(TRASH_DIR))
+            .into_iter()
+            .find(|entry| entry.info.app_id == app_id)
+            .unwrap();
+        let info = TrashInfo {
+            trashed_at,
+            ..entry.info
+        };
+        fs::write(entry.info_path, serde_json::to_string(&info).unwrap()).unwrap();
+    }
+
+    fn trashed_ids(library_root: &Path) -> Vec<String> {
+        let mut ids: Vec<String> = list_trashed_apps(&[library_root.to_path_buf()])
+            .into_iter()
+            .map(|app| app.app_id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn test_trash_and_restore() {
+        let library_root = create_library("restore", &["game"]);
+        let install_path = library_root.join("game");
+        move_to_trash(&library_root, "game", &install_path).unwrap();
+        assert!(!install_path.exists());
+
+        let trashed = list_trashed_apps(std::slice::from_ref(&library_root));
+        assert_eq!(trashed.len(), 1);
+        assert_eq!(trashed[0].app_id, "game");
+        assert_eq!(trashed[0].disk_size, 100);
+        assert_eq!(
+            trashed[0].original_path,
+            install_path.to_string_lossy().to_string()
+        );
+
+        let restored = restore_trashed_app(std::slice::from_ref(&library_root), "game").unwrap();
+        assert_eq!(restored, install_path);
+        assert!(install_path.join("game.exe").exists());
+        assert!(trashed_ids(&library_root).is_empty());
+        assert!(restore_trashed_app(&[library_root], "game").is_err());
+    }
+
+    #[test]
+    fn test_trash_replaces_previous_copy() {
+        let library_root = create_library("replace", &["game"]);
+        let install_path = library_root.join("game");
+        move_to_trash(&library_root, "game", &install_path).unwrap();
+        fs::create_dir_all(&install_path).unwrap();
+        fs::write(install_path.join("new.exe"), "").unwrap();
+        move_to_trash(&library_root, "game", &install_path).unwrap();
+        assert_eq!(trashed_ids(&library_root), vec!["game"]);
+
+        // A failed move keeps the previous copy
+        assert!(move_to_trash(&library_root, "game", &install_path).is_err());
+        assert_eq!(trashed_ids(&library_root), vec!["game"]);
+
+        restore_trashed_app(&[library_root], "game").unwrap();
+        assert!(install_path.join("new.exe").exists());
+    }
+
+    #[test]
+    fn test_purge_old_apps() {
+        let library_root = create_library("age", &["old", "recent"]);
+        for app_id in ["old", "recent"] {
+            move_to_trash(&library_root, app_id, &library_root.join(app_id)).unwrap();
+        }
+        let now = Utc::now().timestamp();
+        set_trashed_at(
+            &library_root,
+            "old",
+            now - (TRASH_MAX_AGE_DAYS * 24 * 60 * 60 + 1),
+        );
+        purge_library_trash(&library_root, now, |_| None);
+        assert_eq!(trashed_ids(&library_root), vec!["recent"]);
+    }
+
+    #[test]
+    fn test_purge_oldest_apps_for_free_space() {
+        let library_root = create_library("space", &["first", "second", "third"]);
+        for (index, app_id) in ["first", "second", "third"].iter().enumerate() {
+            move_to_trash(&library_root, app_id, &library_root.join(app_id)).unwrap();
+            set_trashed_at(
+                &library_root,
+                app_id,
+                Utc::now().timestamp() - 10 + index as i64,
+            );
+        }
+        let now = Utc::now().timestamp();
+
+        // Enough free space, nothing is purged
+        purge_library_trash(&library_root, now, |_| Some((50_000, 100_000)));
+        assert_eq!(trashed_ids(&library_root).len(), 3);
+
+        // Purging the 100 bytes of the oldest app is enough
+        let total_space = 100_000 / TRASH_MIN_FREE_SPACE_PERCENT * 100;
+        purge_library_trash(&library_root, now, |_| Some((99_950, total_space)));
+        assert_eq!(trashed_ids(&library_root), vec!["second", "third"]);
+    }
+}
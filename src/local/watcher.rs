@@ -10,6 +10,10 @@ use zbus::Connection;
 
 use crate::constants::{CLIENT_PATH, LIBRARY_SUBDIR};
 use crate::local::connector::LocalConnector;
+use crate::local::move_jobs::cancel_move_jobs_on_removed_disks;
+use crate::local::permissions::repair_permissions_in_background;
+use crate::local::preserve::restore_preserved_files;
+use crate::local::trash::is_trash_dir;
 use crate::plugin::dbus::CONNECTION;
 use crate::plugin::library_provider::LibraryProvider;
 
@@ -119,6 +123,7 @@ pub async fn start_watcher() {
                 // Give the system a moment to finish mounting/unmounting
                 sleep(Duration::from_secs(3)).await;
                 while rx.try_recv().is_ok() {}
+                cancel_move_jobs_on_removed_disks();
 
                 sync_watch_paths(
                     &connector,
@@ -189,6 +194,9 @@ async fn subscribe_to_drive_signals(tx: mpsc::Sender<WatchEvent>) {
                 if let Ok(args) = signal.args() {
                     log::info!("Drive removed: {}", args.drive_name);
                 }
+                // Stop the moves reading from or writing to the removed drive right away,
+                // they report the failure themselves once cancelled
+                cancel_move_jobs_on_removed_disks();
                 let _ = tx.send(WatchEvent::DriveChanged).await;
             }
             else => {
@@ -219,6 +227,9 @@ fn sync_watch_paths(
     for root in &library_roots {
         if let Ok(entries) = std::fs::read_dir(root) {
             for entry in entries.flatten() {
+                if is_trash_dir(&entry.path()) {
+                    continue;
+                }
                 let is_dir = entry.metadata().map(|m| m.is_dir()).unwrap_or(false);
                 let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
                 if is_dir || is_symlink {
@@ -266,6 +277,24 @@ async fn check_for_changes(connector: &LocalConnector, known_apps: &mut HashSet<
         added,
         removed
     );
+
+    // Put back the files preserved when a reinstalled app was last uninstalled,
+    // and fix the permissions lost when copying a Linux game from Windows
+    for app_id in &added {
+        match connector.find_app(app_id) {
+            Ok(Some(install_path)) => {
+                if let Err(e) = restore_preserved_files(app_id, &install_path) {
+                    log::error!("Failed to restore preserved files of {}: {}", app_id, e);
+                }
+                if let Ok(metadata) = connector.load_metadata(app_id).await {
+                    repair_permissions_in_background(app_id, install_path, metadata);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Failed to find {}: {}", app_id, e),
+        }
+    }
+
     *known_apps = current_apps;
 
     if let Err(e) = emit_installed_apps_updated().await {
@@ -305,6 +334,9 @@ fn list_current_apps(connector: &LocalConnector) -> HashSet<String> {
     for path in paths {
         if let Ok(entries) = std::fs::read_dir(path) {
             for entry in entries.flatten() {
+                if is_trash_dir(&entry.path()) {
+                    continue;
+                }
                 let is_dir = entry.metadata().map(|m| m.is_dir()).unwrap_or(false);
                 let is_symlink = entry.file_type().map(|t| t.is_symlink()).unwrap_or(false);
                 if (is_dir || is_symlink) && entry.path().join("gameinfo.yaml").exists() {
@@ -0,0 +1,191 @@
+//! Minimal WebDAV client used as a save sync target.
+//! Must be used from a blocking context.
+
+use crate::local::cloud_sync::SyncBackend;
+use crate::plugin::errors::PluginError;
+use crate::types::results::{EmptyResult, ResultWithError};
+use regex::Regex;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::{Method, StatusCode, Url};
+use std::time::Duration;
+
+const WEBDAV_TIMEOUT: Duration = Duration::from_secs(60);
+
+const QUOTA_REQUEST: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:propfind xmlns:d="DAV:">
+  <d:prop>
+    <d:quota-available-bytes/>
+    <d:quota-used-bytes/>
+  </d:prop>
+</d:propfind>"#;
+
+pub struct WebDavBackend {
+    client: Client,
+    base_url: Url,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl WebDavBackend {
+    pub fn new(
+        url: &str,
+        username: Option<String>,
+        password: Option<String>,
+    ) -> ResultWithError<Self> {
+        let client = Client::builder().timeout(WEBDAV_TIMEOUT).build()?;
+        Ok(Self {
+            client,
+            base_url: Url::parse(url).map_err(|e| format!("Invalid WebDAV url {}: {}", url, e))?,
+            username,
+            password,
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> ResultWithError<RequestBuilder> {
+        let request = self.client.request(method, self.build_url(path)?);
+        Ok(match &self.username {
+            Some(username) => request.basic_auth(username, self.password.as_ref()),
+            None => request,
+        })
+    }
+
+    /// Returns the URL of a path relative to the base URL, each segment being
+    /// percent-encoded so names such as `slot #1.sav` stay a single segment
+    fn build_url(&self, path: &str) -> ResultWithError<Url> {
+        let mut url = self.base_url.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| format!("Invalid WebDAV url {}", self.base_url))?;
+            segments
+                .pop_if_empty()
+                .extend(path.split('/').filter(|segment| !segment.is_empty()));
+            // Collections, the base one included, keep their trailing slash
+            if path.is_empty() || path.ends_with('/') {
+                segments.push("");
+            }
+        }
+        Ok(url)
+    }
+
+    /// Create the parent collections of a path, WebDAV servers don't do it on PUT
+    fn create_parents(&self, path: &str) -> EmptyResult {
+        let mkcol = Method::from_bytes(b"MKCOL")?;
+        let parts: Vec<&str> = path.split('/').collect();
+        let mut collection = String::new();
+        for part in &parts[..parts.len().saturating_sub(1)] {
+            collection.push_str(part);
+            collection.push('/');
+            let status = self.request(mkcol.clone(), &collection)?.send()?.status();
+            // 405 means the collection already exists
+            if !status.is_success() && status != StatusCode::METHOD_NOT_ALLOWED {
+                return Err(check_status(status, &collection).unwrap_err());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SyncBackend for WebDavBackend {
+    fn read(&self, path: &str) -> ResultWithError<Option<Vec<u8>>> {
+        let response = self.request(Method::GET, path)?.send()?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        check_status(response.status(), path)?;
+        Ok(Some(response.bytes()?.to_vec()))
+    }
+
+    fn write(&self, path: &str, data: &[u8]) -> EmptyResult {
+        let send = || -> ResultWithError<StatusCode> {
+            Ok(self
+                .request(Method::PUT, path)?
+                .body(data.to_vec())
+                .send()?
+                .status())
+        };
+        let mut status = send()?;
+        if status == StatusCode::CONFLICT || status == StatusCode::NOT_FOUND {
+            self.create_parents(path)?;
+            status = send()?;
+        }
+        check_status(status, path)
+    }
+
+    fn delete(&self, path: &str) -> EmptyResult {
+        let status = self.request(Method::DELETE, path)?.send()?.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check_status(status, path)
+    }
+
+    fn usage(&self) -> ResultWithError<Option<(u64, u64)>> {
+        let response = self
+            .request(Method::from_bytes(b"PROPFIND")?, "")?
+            .header("Depth", "0")
+            .header("Content-Type", "application/xml")
+            .body(QUOTA_REQUEST)
+            .send()?;
+        check_status(response.status(), "")?;
+        let body = response.text()?;
+        let used = parse_property(&body, "quota-used-bytes");
+        let available = parse_property(&body, "quota-available-bytes");
+        Ok(used
+            .zip(available)
+            .map(|(used, available)| (used, used + available)))
+    }
+}
+
+fn check_status(status: StatusCode, path: &str) -> EmptyResult {
+    match status {
+        status if status.is_success() => Ok(()),
+        StatusCode::INSUFFICIENT_STORAGE => Err(PluginError::CloudQuota.into()),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            Err(format!("Access to {} was denied by the WebDAV server", path).into())
+        }
+        status => Err(format!("WebDAV request for {} failed: {}", path, status).into()),
+    }
+}
+
+/// Read a numeric property from a PROPFIND response, whatever its namespace prefix.
+/// Servers report a negative or missing value when the quota is unknown.
+fn parse_property(body: &str, name: &str) -> Option<u64> {
+    let regex = Regex::new(&format!(r"<(?:\w+:)?{}[^>]*>\s*(\d+)\s*<", name)).ok()?;
+    regex.captures(body)?[1].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_url() {
+        let backend = WebDavBackend::new(
+            "https://cloud.example.com/remote.php/dav/files/me/playtron/",
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            backend
+                .build_url("my-game/Saved Games/slot #1.sav")
+                .unwrap()
+                .as_str(),
+            "https://cloud.example.com/remote.php/dav/files/me/playtron/my-game/Saved%20Games/slot%20%231.sav"
+        );
+        assert_eq!(
+            backend.build_url("my-game/100%?.sav").unwrap().as_str(),
+            "https://cloud.example.com/remote.php/dav/files/me/playtron/my-game/100%25%3F.sav"
+        );
+        assert_eq!(
+            backend.build_url("my-game/").unwrap().as_str(),
+            "https://cloud.example.com/remote.php/dav/files/me/playtron/my-game/"
+        );
+        assert_eq!(
+            backend.build_url("").unwrap().as_str(),
+            "https://cloud.example.com/remote.php/dav/files/me/playtron/"
+        );
+        assert!(WebDavBackend::new("not a url", None, None).is_err());
+    }
+}
@@ -1,5 +1,6 @@
 use std::env;
 use std::future::pending;
+use std::time::Duration;
 mod auth;
 mod constants;
 mod local;
@@ -8,7 +9,10 @@ mod types;
 mod utils;
 use crate::local::service::LocalService;
 
-use crate::plugin::dbus::{build_connection, register_plugin};
+use crate::plugin::dbus::{build_connection, get_library_provider_emitter, register_plugin};
+
+/// Delay between two attempts to recover moves whose disk is not mounted
+const MOVE_RECOVERY_RETRY_INTERVAL: Duration = Duration::from_secs(60);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
@@ -23,9 +27,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     log::info!("Starting Playtron Plugin version: {version} ");
 
     let local_service = LocalService::new();
-    build_connection(local_service).await?;
+    build_connection(local_service.clone()).await?;
     register_plugin().await;
 
+    // Resume or roll back app moves interrupted by a crash or a restart, in the
+    // background as removing partial copies can take a while. Moves whose disk
+    // isn't mounted are retried until it is.
+    let recovery_service = local_service.clone();
+    tokio::spawn(async move {
+        match get_library_provider_emitter().await {
+            Ok(emitter) => {
+                while recovery_service
+                    .recover_interrupted_moves(emitter.clone())
+                    .await
+                {
+                    tokio::time::sleep(MOVE_RECOVERY_RETRY_INTERVAL).await;
+                }
+            }
+            Err(e) => log::error!("Failed to recover interrupted moves: {}", e),
+        }
+    });
+    local_service.purge_trash();
+
     // Watch local game directories for new/removed apps
     tokio::spawn(local::watcher::start_watcher());
 
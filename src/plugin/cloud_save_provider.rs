@@ -0,0 +1,133 @@
+use crate::local::cloud_sync::SyncFailure;
+use crate::local::service::LocalService;
+use crate::plugin::errors::PluginError;
+use crate::types::cloud_sync::{
+    CloudFailureReason, CloudSyncFailed, CloudSyncOperation, CloudSyncParams, CloudSyncProgress,
+};
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+use zbus_macros::interface;
+
+pub struct CloudSaveProvider {
+    pub service: LocalService,
+}
+
+impl CloudSaveProvider {
+    pub fn new(service: LocalService) -> Self {
+        CloudSaveProvider { service }
+    }
+}
+
+#[interface(name = "one.playtron.plugin.CloudSaveProvider")]
+impl CloudSaveProvider {
+    /// Emitted while the saves of an app are being synced, progress goes from 0.0 to 1.0
+    #[zbus(signal)]
+    pub async fn cloud_save_sync_progressed(
+        emitter: &SignalEmitter<'_>,
+        progress: CloudSyncProgress,
+    ) -> zbus::Result<()>;
+
+    /// Emitted when the saves of an app couldn't be synced.
+    /// `local` and `remote` are set for conflicts, `usage` and `quota` when out of space.
+    #[zbus(signal)]
+    pub async fn cloud_save_sync_failed(
+        emitter: &SignalEmitter<'_>,
+        failure: CloudSyncFailed,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    pub async fn cloud_save_sync_completed(
+        emitter: &SignalEmitter<'_>,
+        app_id: &str,
+    ) -> zbus::Result<()>;
+
+    /// Uploads (1) or downloads (0) the save files of an app to the target
+    /// configured in `cloud_sync.yaml`. Fails with a conflict when the saves
+    /// changed on both sides since the last sync, unless `conflict_resolution`
+    /// is set, in which case the requested direction wins.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.CloudSaveProvider \
+    ///   SyncSaves "ssub" "user" "my-game" 1 false
+    async fn sync_saves(
+        &self,
+        user_id: String,
+        app_id: String,
+        operation: CloudSyncOperation,
+        conflict_resolution: bool,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+    ) -> fdo::Result<()> {
+        let params = CloudSyncParams {
+            user_id,
+            provider_app_id: app_id.clone(),
+            operation,
+            conflict_resolution,
+        };
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let sync = self.service.sync_saves(params, sender);
+        tokio::pin!(sync);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut sync => break result,
+                Some(progress) = receiver.recv() => {
+                    emitter
+                        .cloud_save_sync_progressed(CloudSyncProgress {
+                            app_id: app_id.clone(),
+                            progress,
+                            sync_state: operation,
+                        })
+                        .await?;
+                }
+            }
+        };
+
+        let failure = match result {
+            Ok(_) => {
+                emitter.cloud_save_sync_completed(&app_id).await?;
+                return Ok(());
+            }
+            Err(failure) => failure,
+        };
+        let mut sync_failed = CloudSyncFailed {
+            app_id: app_id.clone(),
+            error: String::new(),
+            local: 0,
+            remote: 0,
+            usage: 0,
+            quota: 0,
+        };
+        let message = match failure {
+            SyncFailure::Reason(CloudFailureReason::Conflict { local, remote }) => {
+                sync_failed.error = PluginError::CloudConflict.to_string();
+                sync_failed.local = local;
+                sync_failed.remote = remote;
+                format!(
+                    "The saves of {} changed locally ({}) and on the target ({})",
+                    app_id, local, remote
+                )
+            }
+            SyncFailure::Reason(CloudFailureReason::Quota { total, quota }) => {
+                sync_failed.error = PluginError::CloudQuota.to_string();
+                sync_failed.usage = total;
+                sync_failed.quota = quota;
+                format!("The saves of {} don't fit on the target", app_id)
+            }
+            SyncFailure::Reason(CloudFailureReason::Disk { needed }) => {
+                sync_failed.error = PluginError::NotEnoughSpace.to_string();
+                sync_failed.usage = needed;
+                format!("Not enough space to download the saves of {}", app_id)
+            }
+            SyncFailure::Error(error, message) => {
+                sync_failed.error = error.to_string();
+                message
+            }
+        };
+        log::error!("Failed to sync the saves of {}: {}", app_id, message);
+        emitter.cloud_save_sync_failed(sync_failed).await?;
+        Err(fdo::Error::Failed(message))
+    }
+}
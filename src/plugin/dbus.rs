@@ -1,8 +1,10 @@
 use crate::constants::{BUS_NAME, CLIENT_PATH};
 use crate::local::service::LocalService;
-use crate::types::results::EmptyResult;
+use crate::plugin::library_provider::LibraryProvider;
+use crate::types::results::{EmptyResult, ResultWithError};
 use crate::{auth, plugin};
 use tokio::sync::Mutex;
+use zbus::object_server::SignalEmitter;
 use zbus::{connection, zvariant::ObjectPath, Connection};
 
 lazy_static::lazy_static! {
@@ -15,6 +17,8 @@ pub async fn build_connection(service: LocalService) -> EmptyResult {
     let plugin = plugin::plugin_interface::Plugin {};
     let cryptography = auth::cryptography::Cryptography::new(service.clone());
     let library_provider = plugin::library_provider::LibraryProvider::new(service.clone());
+    let local_library = plugin::local_library::LocalLibrary::new(service.clone());
+    let cloud_save_provider = plugin::cloud_save_provider::CloudSaveProvider::new(service.clone());
 
     *CONNECTION.lock().await = Some(
         connection::Builder::session()?
@@ -22,6 +26,8 @@ pub async fn build_connection(service: LocalService) -> EmptyResult {
             .serve_at(CLIENT_PATH, plugin)?
             .serve_at(CLIENT_PATH, cryptography)?
             .serve_at(CLIENT_PATH, library_provider)?
+            .serve_at(CLIENT_PATH, local_library)?
+            .serve_at(CLIENT_PATH, cloud_save_provider)?
             .build()
             .await?,
     );
@@ -53,3 +59,14 @@ pub async fn register_plugin() {
         }
     }
 }
+
+/// Returns an emitter for the library provider signals, for use outside of D-Bus method calls
+pub async fn get_library_provider_emitter() -> ResultWithError<SignalEmitter<'static>> {
+    let conn_guard = CONNECTION.lock().await;
+    let conn = conn_guard.as_ref().ok_or("No D-Bus connection")?;
+    let iface_ref = conn
+        .object_server()
+        .interface::<_, LibraryProvider>(CLIENT_PATH)
+        .await?;
+    Ok(iface_ref.signal_emitter().clone())
+}
@@ -55,4 +55,6 @@ pub enum PluginError {
     Permission,
     #[error("one.playtron.Error.NetworkRequired")]
     NetworkRequired,
+    #[error("one.playtron.Error.AppInUse")]
+    AppInUse,
 }
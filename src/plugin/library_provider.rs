@@ -1,6 +1,8 @@
 #![allow(clippy::too_many_arguments)]
 use crate::constants::{LIBRARY_PROVIDER_ID, LIBRARY_PROVIDER_NAME};
+use crate::local::pre_launch::LaunchError;
 use crate::local::service::LocalService;
+use crate::plugin::errors::PluginError;
 use crate::types::app::{DownloadStage, EulaEntry, InstalledApp, LaunchOption, ProviderItem};
 use crate::types::cloud_sync::CloudPath;
 use std::collections::HashMap;
@@ -87,7 +89,9 @@ impl LibraryProvider {
     #[zbus(signal)]
     pub async fn installed_apps_updated(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
 
-    /// LaunchError signal emitted after calling pre_launch_hook
+    /// LaunchError signal emitted after calling pre_launch_hook. The error starts with
+    /// its code, followed by a readable message, e.g.
+    /// `one.playtron.Error.PreLaunchError: The executable "game.exe" was not found`
     #[zbus(signal)]
     pub async fn launch_error(
         emitter: &SignalEmitter<'_>,
@@ -134,6 +138,19 @@ impl LibraryProvider {
         progress: f32,
     ) -> zbus::Result<()>;
 
+    /// Emitted during MoveItem alongside MoveItemProgressed, with the number of bytes
+    /// moved so far out of `total_bytes`, the current transfer speed in bytes per second
+    /// and the estimated time remaining in seconds.
+    #[zbus(signal)]
+    pub async fn move_item_transfer_progressed(
+        emitter: &SignalEmitter<'_>,
+        app_id: String,
+        moved_bytes: u64,
+        total_bytes: u64,
+        bytes_per_second: u64,
+        eta_seconds: u64,
+    ) -> zbus::Result<()>;
+
     /// Retrieve EULAs for an app
     async fn get_eulas(
         &self,
@@ -257,10 +274,14 @@ impl LibraryProvider {
     async fn install(
         &self,
         app_id: &str,
-        _dest_path: &str,
+        dest_path: &str,
         _options: HashMap<String, zbus::zvariant::Value<'_>>,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) -> fdo::Result<i32> {
+        if let Err(e) = self.service.install(app_id, dest_path).await {
+            emitter.install_failed(app_id, &e.to_string()).await?;
+            return Err(fdo::Error::Failed(e.to_string()));
+        }
         // Fake the install since it's manual.
         // Only needed for launch configs
         emitter.install_completed(app_id.to_string()).await?;
@@ -293,7 +314,7 @@ impl LibraryProvider {
 
     async fn cancel_move_item(&self, app_id: &str) -> fdo::Result<()> {
         log::info!("cancel move item for {}", app_id);
-        match self.service.cancel_move_item().await {
+        match self.service.cancel_move_item(app_id).await {
             Ok(_) => Ok(()),
             Err(e) => Err(fdo::Error::Failed(e.to_string())),
         }
@@ -358,25 +379,39 @@ impl LibraryProvider {
         Ok(())
     }
 
-    /// Executed before a game is launched. Should emit LaunchReady signal if the game can be launched.
+    /// Executed before a game is launched. Emits LaunchReady if the game can be launched,
+    /// LaunchError with the reason otherwise.
     async fn pre_launch_hook(
         &self,
         app_id: &str,
         using_offline_mode: bool,
         #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
     ) -> fdo::Result<Vec<String>> {
-        let res = self
+        match self
             .service
             .pre_launch_hook(app_id.to_string(), using_offline_mode)
-            .await;
-        emitter.launch_ready(app_id).await?;
-        res
+            .await
+        {
+            Ok(res) => {
+                emitter.launch_ready(app_id).await?;
+                Ok(res)
+            }
+            Err(e) => {
+                let error = match e.downcast_ref::<LaunchError>() {
+                    Some(launch_error) => launch_error.to_string(),
+                    None => {
+                        LaunchError::new(PluginError::PreLaunchError, e.to_string()).to_string()
+                    }
+                };
+                emitter.launch_error(app_id, &error).await?;
+                Err(fdo::Error::Failed(e.to_string()))
+            }
+        }
     }
 
     /// Executed after a game has launched
     async fn post_launch_hook(&self, app_id: &str) -> fdo::Result<()> {
-        log::info!("post launch hook for {}", app_id);
-        Ok(())
+        self.service.post_launch_hook(app_id).await
     }
 
     async fn sync_installed_apps(&self) -> fdo::Result<()> {
@@ -0,0 +1,142 @@
+use crate::local::service::LocalService;
+use crate::types::app::{MoveStatus, PlaySession, Playtime, SaveSnapshot, TrashedApp};
+use zbus::fdo;
+use zbus_macros::interface;
+
+/// Extra methods specific to the local games plugin
+pub struct LocalLibrary {
+    pub service: LocalService,
+}
+
+impl LocalLibrary {
+    pub fn new(service: LocalService) -> Self {
+        LocalLibrary { service }
+    }
+}
+
+#[interface(name = "one.playtron.plugin.LocalLibrary")]
+impl LocalLibrary {
+    /// Returns the status of the move operation for the given app.
+    /// The state is `Idle` when the app isn't being moved.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetMoveStatus "s" "my-game"
+    async fn get_move_status(&self, app_id: &str) -> fdo::Result<MoveStatus> {
+        Ok(self.service.get_move_status(app_id))
+    }
+
+    /// Returns the apps that were uninstalled and can still be restored.
+    /// Trashed apps are purged automatically after a while or when the disk runs low on space.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetTrashedApps
+    async fn get_trashed_apps(&self) -> fdo::Result<Vec<TrashedApp>> {
+        match self.service.get_trashed_apps().await {
+            Ok(apps) => Ok(apps),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Moves an uninstalled app back to its library and returns its install folder.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   RestoreTrashedApp "s" "my-game"
+    async fn restore_trashed_app(&self, app_id: &str) -> fdo::Result<String> {
+        match self.service.restore_trashed_app(app_id) {
+            Ok(path) => Ok(path),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Returns the local snapshots of the save files of an app, oldest first.
+    /// Snapshots are taken before and after each game session. Fails when the
+    /// saves are inside a Wine prefix the plugin doesn't know about.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetSaveSnapshots "s" "my-game"
+    async fn get_save_snapshots(&self, app_id: &str) -> fdo::Result<Vec<SaveSnapshot>> {
+        match self.service.get_save_snapshots(app_id).await {
+            Ok(snapshots) => Ok(snapshots),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Restores the save files of an app from a snapshot.
+    /// The current save files are snapshotted first.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   RestoreSaveSnapshot "ss" "my-game" "20261018120000000"
+    async fn restore_save_snapshot(&self, app_id: &str, snapshot_id: &str) -> fdo::Result<()> {
+        match self
+            .service
+            .restore_save_snapshot(app_id, snapshot_id)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Returns the total playtime, last played time and number of sessions of an app.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetPlaytime "s" "my-game"
+    async fn get_playtime(&self, app_id: &str) -> fdo::Result<Playtime> {
+        match self.service.get_playtime(app_id) {
+            Ok(playtime) => Ok(playtime),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Returns the start and end times of each finished session of an app, oldest first.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetPlaySessions "s" "my-game"
+    async fn get_play_sessions(&self, app_id: &str) -> fdo::Result<Vec<PlaySession>> {
+        match self.service.get_play_sessions(app_id) {
+            Ok(sessions) => Ok(sessions),
+            Err(err) => Err(fdo::Error::Failed(err.to_string())),
+        }
+    }
+
+    /// Returns the hardware tags matching the current device, launch options
+    /// with one of these tags are meant to be preferred on it.
+    ///
+    /// # Example
+    ///
+    /// busctl --user call one.playtron.LocalPlugin \
+    ///   /one/playtron/LocalPlugin/PluginClient0 \
+    ///   one.playtron.plugin.LocalLibrary \
+    ///   GetHardwareTags
+    async fn get_hardware_tags(&self) -> Vec<String> {
+        self.service.get_hardware_tags()
+    }
+}
@@ -1,4 +1,6 @@
+pub mod cloud_save_provider;
 pub mod dbus;
 pub mod errors;
 pub mod library_provider;
+pub mod local_library;
 pub mod plugin_interface;
@@ -135,6 +135,64 @@ pub struct LaunchOption {
     pub hardware_tags: Vec<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Type, PartialEq, Clone, Copy)]
+/// Current state of an app move job
+pub enum MoveState {
+    Idle = 0,
+    Moving = 1,
+    Cancelling = 2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Type, Clone)]
+pub struct MoveStatus {
+    pub app_id: String,
+    pub state: MoveState,
+    // Folder the app is being moved from, empty when idle
+    pub source_path: String,
+    // Folder the app is being moved to, empty when idle
+    pub destination_path: String,
+    // Progress of the move, from 0.0 to 1.0
+    pub progress: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Type, Clone)]
+pub struct TrashedApp {
+    pub app_id: String,
+    // Folder the app was uninstalled from, and where it is restored to
+    pub original_path: String,
+    // Unix timestamp in seconds of when the app was uninstalled
+    pub trashed_at: i64,
+    pub disk_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Type, Clone)]
+pub struct SaveSnapshot {
+    pub id: String,
+    // Unix timestamp in seconds of when the snapshot was taken
+    pub created_at: i64,
+    // What triggered the snapshot, e.g. "pre_launch" or "post_launch"
+    pub reason: String,
+    pub file_count: u32,
+    pub total_size: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Type, Clone)]
+pub struct PlaySession {
+    // Unix timestamps in seconds of when the game was launched and exited
+    pub started_at: i64,
+    pub ended_at: i64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Type, Clone, Default)]
+pub struct Playtime {
+    pub app_id: String,
+    // Total time played over all sessions, in seconds
+    pub total_seconds: u64,
+    // Unix timestamp in seconds of when the game was last launched, 0 if never played
+    pub last_played: i64,
+    pub session_count: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug, Type)]
 pub struct InstallOptionDescription {
     // Identifier for the install option. This is used to provide
@@ -1,7 +1,7 @@
 use serde::{Deserialize, Serialize};
 use zbus::zvariant::Type;
 
-#[derive(Debug, Serialize, Deserialize, PartialEq, Type)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Type)]
 #[serde(rename_all = "lowercase")]
 pub enum CloudSyncOperation {
     Download,
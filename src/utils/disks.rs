@@ -1,13 +1,104 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
 use sysinfo::Disks;
 
+use crate::plugin::errors::PluginError;
+use crate::types::results::ResultWithError;
+
+/// Returns true for the mount points of removable/external disks
+fn is_external_mount_point(mount_point: &str) -> bool {
+    mount_point.starts_with("/media") || mount_point.starts_with("/run/media")
+}
+
 pub fn get_mount_points() -> Vec<String> {
     let disks = Disks::new_with_refreshed_list();
     let mut mount_points: Vec<String> = Vec::new();
     for disk in &disks {
         let mount_point_str = disk.mount_point().to_str().unwrap_or_default().to_string();
-        if mount_point_str.starts_with("/media") || mount_point_str.starts_with("/run/media") {
+        if is_external_mount_point(&mount_point_str) {
             mount_points.push(mount_point_str);
         }
     }
     mount_points
 }
+
+/// Returns false when the given path, once symlinks are resolved, is missing or
+/// lives under the mount point of an external disk that isn't mounted anymore
+pub fn is_path_mounted(path: &Path) -> bool {
+    let Ok(path) = fs::canonicalize(path) else {
+        return false;
+    };
+    if !is_external_mount_point(&path.to_string_lossy()) {
+        return true;
+    }
+    get_mount_points()
+        .iter()
+        .any(|mount_point| path.starts_with(mount_point))
+}
+
+/// Returns the device id of the filesystem holding the given path.
+/// If the path doesn't exist yet, the closest existing ancestor is used.
+pub fn get_device_id(path: &Path) -> Option<u64> {
+    path.ancestors()
+        .find_map(|ancestor| fs::metadata(ancestor).ok())
+        .map(|metadata| metadata.dev())
+}
+
+/// Returns the available and total space, in bytes, of the disk holding the given path
+pub fn get_disk_space(path: &Path) -> Option<(u64, u64)> {
+    // The disk holding the path is the one with the longest matching mount point
+    Disks::new_with_refreshed_list()
+        .iter()
+        .filter(|disk| path.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| (disk.available_space(), disk.total_space()))
+}
+
+/// Where a block device or a mount root is mounted
+pub enum MountTarget {
+    /// The disk holding the home directory
+    Internal,
+    /// A removable/external disk mounted at the given path
+    External(PathBuf),
+}
+
+/// Returns where the given block device (e.g. `/dev/sda3`) or mount root is mounted.
+/// Returns `None` if the path is neither a known block device nor a mount root.
+/// Block devices that aren't mounted return a `DiskNotFound` error.
+pub fn find_mount_target(path: &Path) -> ResultWithError<Option<MountTarget>> {
+    let is_device = path.starts_with("/dev");
+    // Resolve /dev/disk/by-* links to the actual device node
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+
+    let disks = Disks::new_with_refreshed_list();
+    let mount_points: Vec<&Path> = disks
+        .iter()
+        .filter(|disk| {
+            if is_device {
+                let name = Path::new(disk.name());
+                fs::canonicalize(name).unwrap_or_else(|_| name.to_path_buf()) == path
+            } else {
+                disk.mount_point() == path
+            }
+        })
+        .map(|disk| disk.mount_point())
+        .collect();
+
+    if mount_points.is_empty() {
+        return if is_device {
+            Err(PluginError::DiskNotFound.into())
+        } else {
+            Ok(None)
+        };
+    }
+
+    // A device can be mounted more than once (e.g. btrfs subvolumes), prefer the external mount
+    let external = mount_points
+        .iter()
+        .find(|mount_point| is_external_mount_point(&mount_point.to_string_lossy()));
+    Ok(Some(match external {
+        Some(mount_point) => MountTarget::External(mount_point.to_path_buf()),
+        None => MountTarget::Internal,
+    }))
+}
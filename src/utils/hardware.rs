@@ -0,0 +1,56 @@
+use std::fs;
+
+/// Hardware tags launch options can be marked with
+pub const HARDWARE_TAGS: [&str; 5] = ["steamdeck", "handheld", "desktop", "x86_64", "aarch64"];
+
+const DMI_DIR: &str = "/sys/class/dmi/id";
+
+/// DMI vendors or product names of handheld PCs
+const HANDHELD_IDS: [&str; 9] = [
+    "AYANEO",
+    "AYN",
+    "GPD",
+    "ONE-NETBOOK",
+    "ROG Ally",
+    "RC71L",
+    "RC72LA",
+    "83E1",
+    "Claw",
+];
+
+/// Returns true for the tags defined in `HARDWARE_TAGS`
+pub fn is_valid_hardware_tag(tag: &str) -> bool {
+    HARDWARE_TAGS.contains(&tag)
+}
+
+/// Returns the hardware tags matching the current device
+pub fn get_device_hardware_tags() -> Vec<String> {
+    let vendor = read_dmi("sys_vendor");
+    let board_vendor = read_dmi("board_vendor");
+    let product = read_dmi("product_name");
+
+    let is_steamdeck = (vendor == "Valve" || board_vendor == "Valve")
+        && (product == "Jupiter" || product == "Galileo");
+    let is_handheld = is_steamdeck
+        || HANDHELD_IDS
+            .iter()
+            .any(|id| vendor.starts_with(id) || product.contains(id));
+
+    let mut tags = Vec::new();
+    if is_steamdeck {
+        tags.push("steamdeck");
+    }
+    tags.push(if is_handheld { "handheld" } else { "desktop" });
+    match std::env::consts::ARCH {
+        "x86_64" => tags.push("x86_64"),
+        "aarch64" => tags.push("aarch64"),
+        _ => {}
+    }
+    tags.into_iter().map(String::from).collect()
+}
+
+fn read_dmi(name: &str) -> String {
+    fs::read_to_string(format!("{}/{}", DMI_DIR, name))
+        .map(|value| value.trim().to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,349 @@
+//! Parser for Windows shortcuts (.lnk), following the Shell Link Binary File
+//! Format (MS-SHLLINK). Only the parts needed to launch the target are read.
+
+use crate::types::results::ResultWithError;
+
+const HEADER_SIZE: usize = 0x4c;
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const ENVIRONMENT_VARIABLE_BLOCK: u32 = 0xa0000001;
+
+#[derive(Debug, Default, Clone)]
+pub struct Shortcut {
+    /// Absolute Windows path of the target, e.g. `C:\Games\MyGame\game.exe`
+    pub target: Option<String>,
+    /// Path of the target relative to the shortcut, e.g. `.\bin\game.exe`
+    pub relative_path: Option<String>,
+    pub working_directory: Option<String>,
+    pub arguments: Option<String>,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, length: usize) -> ResultWithError<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or("Unexpected end of shortcut")?;
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> ResultWithError<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> ResultWithError<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    /// Read a StringData structure, a character count followed by the characters
+    fn string_data(&mut self, is_unicode: bool) -> ResultWithError<String> {
+        let count = self.u16()? as usize;
+        Ok(match is_unicode {
+            true => decode_utf16(self.bytes(count * 2)?),
+            false => decode_ansi(self.bytes(count)?),
+        })
+    }
+}
+
+/// Parse the content of a .lnk file
+pub fn parse_shortcut(data: &[u8]) -> ResultWithError<Shortcut> {
+    let mut reader = Reader { data, position: 0 };
+    if reader.u32()? as usize != HEADER_SIZE || reader.bytes(16)? != LINK_CLSID {
+        return Err("Not a Windows shortcut".into());
+    }
+    let flags = reader.u32()?;
+    reader.position = HEADER_SIZE;
+
+    if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+        let size = reader.u16()? as usize;
+        reader.bytes(size)?;
+    }
+
+    let mut shortcut = Shortcut::default();
+    if flags & HAS_LINK_INFO != 0 {
+        // The offsets inside of LinkInfo are relative to its start and must stay inside of it
+        let size = reader.u32()? as usize;
+        reader.position -= 4;
+        shortcut.target = parse_link_info(reader.bytes(size)?)?;
+    }
+
+    let is_unicode = flags & IS_UNICODE != 0;
+    if flags & HAS_NAME != 0 {
+        reader.string_data(is_unicode)?;
+    }
+    if flags & HAS_RELATIVE_PATH != 0 {
+        shortcut.relative_path = Some(reader.string_data(is_unicode)?);
+    }
+    if flags & HAS_WORKING_DIR != 0 {
+        shortcut.working_directory = Some(reader.string_data(is_unicode)?);
+    }
+    if flags & HAS_ARGUMENTS != 0 {
+        shortcut.arguments = Some(reader.string_data(is_unicode)?);
+    }
+    if flags & HAS_ICON_LOCATION != 0 {
+        reader.string_data(is_unicode)?;
+    }
+
+    // Targets using environment variables (e.g. %ProgramFiles%) are stored in an extra data block
+    if shortcut.target.is_none() {
+        while let Ok(size) = reader.u32() {
+            if size < 8 {
+                break;
+            }
+            let block = reader.bytes(size as usize - 4)?;
+            if u32::from_le_bytes(block[..4].try_into()?) == ENVIRONMENT_VARIABLE_BLOCK {
+                // TargetAnsi is 260 bytes, followed by TargetUnicode
+                let target = match block.get(264..784) {
+                    Some(unicode) => decode_utf16(unicode),
+                    None => decode_ansi(block.get(4..264).unwrap_or_default()),
+                };
+                shortcut.target = Some(target).filter(|target| !target.is_empty());
+                break;
+            }
+        }
+    }
+
+    if shortcut.target.is_none() && shortcut.relative_path.is_none() {
+        return Err("The shortcut has no target path".into());
+    }
+    Ok(shortcut)
+}
+
+/// Returns the local path stored in a LinkInfo structure
+fn parse_link_info(data: &[u8]) -> ResultWithError<Option<String>> {
+    let mut reader = Reader { data, position: 4 };
+    let header_size = reader.u32()? as usize;
+    let flags = reader.u32()?;
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH == 0 {
+        return Ok(None);
+    }
+    reader.u32()?; // VolumeIDOffset
+    let base_path_offset = reader.u32()? as usize;
+    reader.u32()?; // CommonNetworkRelativeLinkOffset
+    let suffix_offset = reader.u32()? as usize;
+
+    let (base_path, suffix) = if header_size >= 0x24 {
+        let base_path_offset = reader.u32()? as usize;
+        let suffix_offset = reader.u32()? as usize;
+        (
+            read_null_terminated_utf16(data, base_path_offset)?,
+            read_null_terminated_utf16(data, suffix_offset)?,
+        )
+    } else {
+        (
+            read_null_terminated_ansi(data, base_path_offset)?,
+            read_null_terminated_ansi(data, suffix_offset)?,
+        )
+    };
+    Ok(Some(base_path + &suffix))
+}
+
+fn read_null_terminated_ansi(data: &[u8], offset: usize) -> ResultWithError<String> {
+    let data = data.get(offset..).ok_or("Invalid shortcut string offset")?;
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    Ok(decode_ansi(&data[..end]))
+}
+
+fn read_null_terminated_utf16(data: &[u8], offset: usize) -> ResultWithError<String> {
+    let data = data.get(offset..).ok_or("Invalid shortcut string offset")?;
+    let end = data
+        .chunks_exact(2)
+        .position(|c| c == [0, 0])
+        .map(|index| index * 2)
+        .unwrap_or(data.len());
+    Ok(decode_utf16(&data[..end]))
+}
+
+fn decode_utf16(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|unit| *unit != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// ANSI strings use the system code page, which is unknown here. Latin-1 is
+/// close enough for the ASCII paths of most games.
+fn decode_ansi(data: &[u8]) -> String {
+    data.iter()
+        .take_while(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(flags: u32) -> Vec<u8> {
+        let mut data = (HEADER_SIZE as u32).to_le_bytes().to_vec();
+        data.extend_from_slice(&LINK_CLSID);
+        data.extend_from_slice(&flags.to_le_bytes());
+        data.resize(HEADER_SIZE, 0);
+        data
+    }
+
+    fn string_data(value: &str, is_unicode: bool) -> Vec<u8> {
+        let mut data = Vec::new();
+        match is_unicode {
+            true => {
+                let units: Vec<u16> = value.encode_utf16().collect();
+                data.extend_from_slice(&(units.len() as u16).to_le_bytes());
+                units
+                    .iter()
+                    .for_each(|unit| data.extend_from_slice(&unit.to_le_bytes()));
+            }
+            false => {
+                data.extend_from_slice(&(value.len() as u16).to_le_bytes());
+                data.extend_from_slice(value.as_bytes());
+            }
+        }
+        data
+    }
+
+    /// LinkInfo with a local base path, the strings being stored in UTF-16 when `is_unicode` is set
+    fn link_info(base_path: &str, suffix: &str, is_unicode: bool) -> Vec<u8> {
+        let header_size: u32 = if is_unicode { 0x24 } else { 0x1c };
+        let volume_id = [0x11, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0, 0x10, 0, 0, 0, 0];
+        let mut strings = Vec::new();
+        let mut offsets = Vec::new();
+        let start = header_size as usize + volume_id.len();
+        for value in [base_path, suffix] {
+            offsets.push((start + strings.len()) as u32);
+            strings.extend_from_slice(value.as_bytes());
+            strings.push(0);
+        }
+        if is_unicode {
+            for value in [base_path, suffix] {
+                offsets.push((start + strings.len()) as u32);
+                value
+                    .encode_utf16()
+                    .chain([0])
+                    .for_each(|unit| strings.extend_from_slice(&unit.to_le_bytes()));
+            }
+        }
+
+        let mut fields = vec![
+            header_size,
+            VOLUME_ID_AND_LOCAL_BASE_PATH,
+            header_size,
+            offsets[0],
+            0,
+            offsets[1],
+        ];
+        fields.extend_from_slice(&offsets[2..]);
+        let size = (start + strings.len()) as u32;
+        let mut data = size.to_le_bytes().to_vec();
+        fields
+            .iter()
+            .for_each(|field| data.extend_from_slice(&field.to_le_bytes()));
+        data.extend_from_slice(&volume_id);
+        data.extend_from_slice(&strings);
+        data
+    }
+
+    fn full_shortcut() -> Vec<u8> {
+        let mut data = header(HAS_LINK_INFO | HAS_WORKING_DIR | HAS_ARGUMENTS | IS_UNICODE);
+        data.extend(link_info("C:\\Games\\Ünïcödé\\", "Game.exe", true));
+        data.extend(string_data("C:\\Games\\Ünïcödé", true));
+        data.extend(string_data("-lang=日本語 -windowed", true));
+        data
+    }
+
+    #[test]
+    fn test_parse_minimal_shortcut() {
+        let mut data = header(HAS_RELATIVE_PATH);
+        data.extend(string_data(".\\bin\\game.exe", false));
+        let shortcut = parse_shortcut(&data).unwrap();
+        assert_eq!(shortcut.relative_path.as_deref(), Some(".\\bin\\game.exe"));
+        assert_eq!(shortcut.target, None);
+        assert_eq!(shortcut.working_directory, None);
+        assert_eq!(shortcut.arguments, None);
+    }
+
+    #[test]
+    fn test_parse_shortcut_with_link_info() {
+        let shortcut = parse_shortcut(&full_shortcut()).unwrap();
+        assert_eq!(
+            shortcut.target.as_deref(),
+            Some("C:\\Games\\Ünïcödé\\Game.exe")
+        );
+        assert_eq!(
+            shortcut.working_directory.as_deref(),
+            Some("C:\\Games\\Ünïcödé")
+        );
+        assert_eq!(
+            shortcut.arguments.as_deref(),
+            Some("-lang=日本語 -windowed")
+        );
+
+        let mut data = header(HAS_LINK_TARGET_ID_LIST | HAS_LINK_INFO);
+        data.extend_from_slice(&[4, 0, 2, 0, 0, 0]);
+        data.extend(link_info("C:\\Games\\", "game.exe", false));
+        let shortcut = parse_shortcut(&data).unwrap();
+        assert_eq!(shortcut.target.as_deref(), Some("C:\\Games\\game.exe"));
+    }
+
+    #[test]
+    fn test_parse_shortcut_with_environment_target() {
+        let mut block = vec![0; 0x314];
+        block[..4].copy_from_slice(&0x314u32.to_le_bytes());
+        block[4..8].copy_from_slice(&ENVIRONMENT_VARIABLE_BLOCK.to_le_bytes());
+        let target = "%ProgramFiles%\\Game\\game.exe";
+        block[8..8 + target.len()].copy_from_slice(target.as_bytes());
+        for (index, unit) in target.encode_utf16().enumerate() {
+            block[268 + index * 2..270 + index * 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        let mut data = header(0);
+        data.extend_from_slice(&block);
+        data.extend_from_slice(&[0; 4]);
+        let shortcut = parse_shortcut(&data).unwrap();
+        assert_eq!(shortcut.target.as_deref(), Some(target));
+
+        data.truncate(HEADER_SIZE + 0x100);
+        assert!(parse_shortcut(&data).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid_shortcut() {
+        let data = full_shortcut();
+        for length in 0..data.len() {
+            assert!(parse_shortcut(&data[..length]).is_err(), "{}", length);
+        }
+        assert!(parse_shortcut(b"MZ\x90\x00").is_err());
+        // No target at all
+        assert!(parse_shortcut(&header(0)).is_err());
+
+        // LinkInfo larger than the file
+        let mut invalid = data.clone();
+        invalid[HEADER_SIZE..HEADER_SIZE + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_shortcut(&invalid).is_err());
+        // Base path offset outside of LinkInfo
+        let mut invalid = data.clone();
+        invalid[HEADER_SIZE + 28..HEADER_SIZE + 32].copy_from_slice(&0xffffu32.to_le_bytes());
+        assert!(parse_shortcut(&invalid).is_err());
+        // ID list larger than the file
+        let mut invalid = header(HAS_LINK_TARGET_ID_LIST | HAS_RELATIVE_PATH);
+        invalid.extend_from_slice(&[0xff, 0xff, 0, 0]);
+        assert!(parse_shortcut(&invalid).is_err());
+    }
+}
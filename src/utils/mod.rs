@@ -1,3 +1,7 @@
 pub mod date;
 pub mod disks;
+pub mod hardware;
+pub mod lnk;
+pub mod paths;
 pub mod system;
+pub mod templates;
@@ -0,0 +1,93 @@
+use crate::types::results::ResultWithError;
+use std::fs;
+use std::path::Path;
+
+/// Resolve a path written for Windows (e.g. `Bin\Game.EXE`) against the files
+/// actually inside `base`. Separators are normalized and each component is
+/// matched case-insensitively when there is no exact match.
+/// Returns the path relative to `base` as it exists on disk, with `/` separators.
+pub fn resolve_relative_path(base: &Path, path: &str) -> ResultWithError<String> {
+    let normalized = path.replace('\\', "/");
+    let mut resolved: Vec<String> = Vec::new();
+
+    for component in normalized.split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => {
+                resolved
+                    .pop()
+                    .ok_or_else(|| format!("{} points outside of {:?}", path, base))?;
+                continue;
+            }
+            _ => {}
+        }
+
+        let current = resolved.iter().fold(base.to_path_buf(), |p, c| p.join(c));
+        if current.join(component).symlink_metadata().is_ok() {
+            resolved.push(component.to_string());
+            continue;
+        }
+
+        let matches: Vec<String> = fs::read_dir(&current)
+            .map_err(|_| format!("{} was not found in {:?}", path, base))?
+            .flatten()
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.to_lowercase() == component.to_lowercase())
+            .collect();
+        match matches.as_slice() {
+            [] => return Err(format!("{} was not found in {:?}", path, base).into()),
+            [name] => resolved.push(name.clone()),
+            _ => {
+                return Err(format!(
+                    "{} is ambiguous, several files match in {:?}: {}",
+                    path,
+                    current,
+                    matches.join(", ")
+                )
+                .into())
+            }
+        }
+    }
+
+    if resolved.is_empty() {
+        return Err(format!("{} doesn't point to a file", path).into());
+    }
+    Ok(resolved.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_relative_path() {
+        let base = std::env::temp_dir().join(format!("resolve-path-{}", std::process::id()));
+        fs::create_dir_all(base.join("bin/x64")).unwrap();
+        fs::write(base.join("bin/x64/game.exe"), "").unwrap();
+        fs::write(base.join("Data.pak"), "").unwrap();
+        fs::write(base.join("data.PAK"), "").unwrap();
+
+        assert_eq!(
+            resolve_relative_path(&base, "bin/x64/game.exe").unwrap(),
+            "bin/x64/game.exe"
+        );
+        assert_eq!(
+            resolve_relative_path(&base, "Bin\\X64\\Game.EXE").unwrap(),
+            "bin/x64/game.exe"
+        );
+        assert_eq!(
+            resolve_relative_path(&base, ".\\bin\\..\\bin\\x64\\GAME.exe").unwrap(),
+            "bin/x64/game.exe"
+        );
+        // Exact matches win over case-insensitive ones
+        assert_eq!(
+            resolve_relative_path(&base, "Data.pak").unwrap(),
+            "Data.pak"
+        );
+
+        assert!(resolve_relative_path(&base, "DATA.pak").is_err());
+        assert!(resolve_relative_path(&base, "bin/missing.exe").is_err());
+        assert!(resolve_relative_path(&base, "../game.exe").is_err());
+        assert!(resolve_relative_path(&base, "./").is_err());
+    }
+}
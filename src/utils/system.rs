@@ -1,8 +1,16 @@
-use crate::types::results::{ResultWithError, U8Result};
+use crate::constants::PLUGIN_DATA_SUBDIR;
+use crate::types::results::{EmptyResult, ResultWithError};
+use crate::utils::disks::get_device_id;
 use async_stream::try_stream;
 use futures::Stream;
 use regex::Regex;
-use std::{path::PathBuf, pin::Pin, process::Stdio};
+use std::os::unix::fs::MetadataExt;
+use std::{
+    path::{Path, PathBuf},
+    pin::Pin,
+    process::Stdio,
+    time::{Duration, Instant},
+};
 use tokio::{
     fs::{self},
     io::AsyncReadExt,
@@ -18,26 +26,196 @@ pub fn get_folder_name(path: PathBuf) -> Option<String> {
     None
 }
 
-/// Move app from one directory to another
+/// Returns the given folder inside the plugin data directory, creating it if needed
+pub fn get_plugin_data_dir(subdir: &str) -> ResultWithError<PathBuf> {
+    let data_dir = dirs::data_dir()
+        .ok_or("Can't get data dir")?
+        .join(PLUGIN_DATA_SUBDIR)
+        .join(subdir);
+    std::fs::create_dir_all(&data_dir)?;
+    Ok(data_dir)
+}
+
+/// Point the plugin data directory to a temporary folder for the tests
+#[cfg(test)]
+pub fn use_test_data_dir() {
+    static INIT: std::sync::Once = std::sync::Once::new();
+    INIT.call_once(|| {
+        let dir =
+            std::env::temp_dir().join(format!("playtron-plugin-local-{}", std::process::id()));
+        std::env::set_var("XDG_DATA_HOME", dir);
+    });
+}
+
+/// Minimum delay between two progress reports that don't change the percentage
+const MOVE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Moved bytes, percentage and throughput of an rsync `--info=progress2` line
+const RSYNC_PROGRESS_PATTERN: &str = r"^\s*([\d,]+)\s+(\d+)%\s+([\d.]+)([kMGT]?B)/s";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MoveProgress {
+    /// Progress, from 0 to 100
+    pub percent: u8,
+    /// Bytes copied to the destination so far
+    pub moved_bytes: u64,
+    /// Size of the folder being moved, 0 if unknown
+    pub total_bytes: u64,
+    /// Current transfer speed
+    pub bytes_per_second: u64,
+    /// Estimated time remaining, in seconds
+    pub eta_seconds: u64,
+}
+
+impl MoveProgress {
+    /// Progress from 0.0 to 1.0, based on the byte counts when they are known
+    pub fn fraction(&self) -> f32 {
+        if self.total_bytes > 0 {
+            (self.moved_bytes as f64 / self.total_bytes as f64).min(1.0) as f32
+        } else {
+            f32::from(self.percent) / 100.0
+        }
+    }
+}
+
+/// Parse an rsync `--info=progress2` line such as
+/// `  1,234,567  45%   12.34MB/s    0:01:23`
+fn parse_rsync_progress(line: &str, regex: &Regex, total_bytes: u64) -> Option<MoveProgress> {
+    let captures = regex.captures(line)?;
+    let moved_bytes = captures[1].replace(',', "").parse::<u64>().ok()?;
+    let percent = captures[2].parse::<u8>().ok()?;
+    let rate = captures[3].parse::<f64>().ok()?;
+    let multiplier: f64 = match &captures[4] {
+        "kB" => 1024.0,
+        "MB" => 1024.0 * 1024.0,
+        "GB" => 1024.0 * 1024.0 * 1024.0,
+        "TB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => 1.0,
+    };
+    let bytes_per_second = (rate * multiplier) as u64;
+    let total_bytes = total_bytes.max(moved_bytes);
+    let eta_seconds = match bytes_per_second {
+        0 => 0,
+        rate => (total_bytes - moved_bytes) / rate,
+    };
+    Some(MoveProgress {
+        percent,
+        moved_bytes,
+        total_bytes,
+        bytes_per_second,
+        eta_seconds,
+    })
+}
+
+/// Returns the number of bytes rsync has to copy. When resuming an interrupted move,
+/// the files already in the destination are skipped and not counted by rsync.
+fn get_transfer_size(from: &str, to: &str) -> u64 {
+    let source_size = fs_extra::dir::get_size(from).unwrap_or_else(|e| {
+        log::warn!("Failed to get size of {}: {}", from, e);
+        0
+    });
+    let copied_size = fs_extra::dir::get_size(to).unwrap_or(0);
+    source_size.saturating_sub(copied_size)
+}
+
+/// Copy a file or a whole folder, overwriting existing files at the destination
+pub fn copy_recursive(from: &Path, to: &Path) -> EmptyResult {
+    if from.is_dir() {
+        std::fs::create_dir_all(to)?;
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(from, to)?;
+    }
+    Ok(())
+}
+
+/// Returns true when both paths live on the same filesystem (same device id)
+pub async fn is_same_filesystem(a: &Path, b: &Path) -> bool {
+    match (fs::metadata(a).await, fs::metadata(b).await) {
+        (Ok(a_meta), Ok(b_meta)) => a_meta.dev() == b_meta.dev(),
+        _ => false,
+    }
+}
+
+/// Move app from one directory to another.
+/// When both locations are on the same filesystem the folder is renamed in place,
+/// otherwise the files are copied with rsync and the source is removed afterwards.
+/// `on_copied` is called once all files are copied, right before the source is removed,
+/// or right after the folder was renamed.
 pub async fn move_folder_with_progress(
     from: &str,
     to: &str,
     cancel_token: CancellationToken,
-) -> Pin<Box<impl Stream<Item = ResultWithError<U8Result>>>> {
+    on_copied: impl FnOnce() -> EmptyResult + Send + 'static,
+) -> Pin<Box<impl Stream<Item = ResultWithError<ResultWithError<MoveProgress>>>>> {
     log::info!("Moving folder from: {} to: {}", from, to);
 
-    let mut from = String::from(from);
+    let from = String::from(from);
+    let to = String::from(to);
+
+    let stream = try_stream! {
+        let to_parent = Path::new(&to)
+            .parent()
+            .ok_or("Invalid destination path")?
+            .to_path_buf();
+        fs::create_dir_all(&to_parent).await?;
+
+        // rename fails on a non empty destination, rsync merges into it instead
+        let destination_exists = fs::symlink_metadata(&to).await.is_ok();
+        if destination_exists {
+            log::info!("{} already exists, copying instead of renaming", to);
+        }
+        if !destination_exists && is_same_filesystem(Path::new(&from), &to_parent).await {
+            log::info!("Source and destination are on the same filesystem, renaming");
+            fs::rename(&from, &to).await?;
+            on_copied()?;
+            yield Ok(MoveProgress {
+                percent: 100,
+                ..Default::default()
+            });
+        } else {
+            for await progress in rsync_folder_with_progress(from, to, cancel_token, on_copied) {
+                yield progress?;
+            }
+        }
+    };
+
+    Box::pin(stream)
+}
+
+/// Copy a folder with rsync, reporting progress, and remove the source once done
+fn rsync_folder_with_progress(
+    from: String,
+    to: String,
+    cancel_token: CancellationToken,
+    on_copied: impl FnOnce() -> EmptyResult + Send + 'static,
+) -> impl Stream<Item = ResultWithError<ResultWithError<MoveProgress>>> {
+    let mut from = from;
     if !from.ends_with("/") {
         from.push('/');
     }
-    let to = String::from(to);
 
-    let stream = try_stream! {
+    try_stream! {
         // ensure the new folder exists
         fs::create_dir_all(&to).await?;
+        let to_device = get_device_id(Path::new(&to));
+
+        // rsync only knows the total size of the files it has scanned so far,
+        // so measure the whole folder upfront for accurate byte counts
+        let (size_from, size_to) = (from.clone(), to.clone());
+        let total_bytes =
+            tokio::task::spawn_blocking(move || get_transfer_size(&size_from, &size_to)).await?;
 
         // move the files to the new location
         let mut command = Command::new("rsync")
+            // The progress pattern expects the number format of the C locale
+            .env("LC_ALL", "C")
             .arg("-a")
             .arg("--info=progress2")
             .arg(&from)
@@ -47,28 +225,39 @@ pub async fn move_folder_with_progress(
             .spawn()?;
 
         let stdout = command.stdout.as_mut().unwrap();
-        let progress_regex = Regex::new(r"\s(\d+)%\s").unwrap();
+        let progress_regex = Regex::new(RSYNC_PROGRESS_PATTERN).unwrap();
 
         let mut buffer = [0; 1];
         let mut line = Vec::new();
-        let mut last_progress = 0;
+        let mut last_progress = MoveProgress::default();
+        let mut last_report = Instant::now();
 
-        while stdout.read(&mut buffer).await? > 0 {
+        loop {
+            // Don't wait for rsync output to notice a cancellation, rsync can hang
+            // when the disk it reads from or writes to disappears
+            let read = tokio::select! {
+                read = stdout.read(&mut buffer) => read,
+                _ = cancel_token.cancelled() => Ok(0),
+            };
+            let read = read?;
             if cancel_token.is_cancelled() {
                 log::info!("Cancelling rsync process...");
                 command.kill().await.ok();
                 break;
             }
+            if read == 0 {
+                break;
+            }
             if buffer[0] == b'\r' || buffer[0] == b'\n' {
                 let progress = String::from_utf8_lossy(&line);
 
-                if let Some(Ok(progress)) = progress_regex
-                    .captures(&progress)
-                    .and_then(|cap| cap.get(1))
-                    .map(|progress| progress.as_str().parse::<u8>())
-                {
-                    if progress != last_progress {
-                        last_progress = progress;
+                if let Some(progress) = parse_rsync_progress(&progress, &progress_regex, total_bytes) {
+                    if progress.percent != last_progress.percent
+                        || (progress.moved_bytes != last_progress.moved_bytes
+                            && last_report.elapsed() >= MOVE_PROGRESS_INTERVAL)
+                    {
+                        last_progress = progress.clone();
+                        last_report = Instant::now();
                         yield Ok(progress);
                     }
                 }
@@ -85,15 +274,118 @@ pub async fn move_folder_with_progress(
         let has_err = !output.status.success();
 
         if cancel_token.is_cancelled() || has_err {
-            fs::remove_dir_all(&to).await?;
+            // Never clean up a path whose disk was unmounted in the meantime
+            if get_device_id(Path::new(&to)) == to_device {
+                fs::remove_dir_all(&to).await?;
+            } else {
+                log::warn!("{} is no longer mounted, skipping cleanup", to);
+            }
             let stdout = std::str::from_utf8(&output.stdout)?;
             let stderr = std::str::from_utf8(&output.stderr)?;
             log::error!("Error moving app {}, status:{}, stdout:{}", stderr, output.status, stdout);
             yield Err("App move cancelled".into());
         } else {
+            on_copied()?;
             fs::remove_dir_all(&from).await?;
         }
-    };
+    }
+}
 
-    Box::pin(stream)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rename_calls_on_copied() {
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let dir = std::env::temp_dir().join(format!("move-rename-{}", std::process::id()));
+        let from = dir.join("a/game");
+        let to = dir.join("b/game");
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::write(from.join("game.exe"), "").unwrap();
+
+        let copied = Arc::new(AtomicBool::new(false));
+        let on_copied = {
+            let copied = copied.clone();
+            move || {
+                copied.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+        let mut progress = move_folder_with_progress(
+            from.to_str().unwrap(),
+            to.to_str().unwrap(),
+            CancellationToken::new(),
+            on_copied,
+        )
+        .await;
+        while let Some(result) = progress.next().await {
+            result.unwrap().unwrap();
+        }
+        assert!(copied.load(Ordering::SeqCst));
+        assert!(to.join("game.exe").exists());
+        assert!(!from.exists());
+    }
+
+    #[test]
+    fn test_get_transfer_size() {
+        let dir = std::env::temp_dir().join(format!("transfer-size-{}", std::process::id()));
+        let (from, to) = (dir.join("from"), dir.join("to"));
+        std::fs::create_dir_all(&from).unwrap();
+        std::fs::create_dir_all(&to).unwrap();
+        std::fs::write(from.join("a"), vec![0; 1000]).unwrap();
+        std::fs::write(from.join("b"), vec![0; 500]).unwrap();
+        let (from, to) = (from.to_str().unwrap(), to.to_str().unwrap());
+        assert_eq!(get_transfer_size(from, to), 1500);
+
+        // Resuming, `a` was already copied
+        std::fs::write(dir.join("to/a"), vec![0; 1000]).unwrap();
+        assert_eq!(get_transfer_size(from, to), 500);
+        assert_eq!(get_transfer_size(to, from), 0);
+    }
+
+    #[test]
+    fn test_parse_rsync_progress() {
+        let regex = Regex::new(RSYNC_PROGRESS_PATTERN).unwrap();
+        let progress = parse_rsync_progress(
+            "  1,048,576  25%    2.00MB/s    0:00:01 (xfr#1, to-chk=3/5)",
+            &regex,
+            4 * 1024 * 1024,
+        );
+        assert_eq!(
+            progress,
+            Some(MoveProgress {
+                percent: 25,
+                moved_bytes: 1024 * 1024,
+                total_bytes: 4 * 1024 * 1024,
+                bytes_per_second: 2 * 1024 * 1024,
+                eta_seconds: 1,
+            })
+        );
+
+        let progress = parse_rsync_progress("        512 100%  512.00B/s    0:00:00", &regex, 0);
+        assert_eq!(
+            progress,
+            Some(MoveProgress {
+                percent: 100,
+                moved_bytes: 512,
+                total_bytes: 512,
+                bytes_per_second: 512,
+                eta_seconds: 0,
+            })
+        );
+
+        // No throughput yet
+        let progress = parse_rsync_progress("          0   0%    0.00kB/s    0:00:00", &regex, 100);
+        assert_eq!(progress.map(|progress| progress.eta_seconds), Some(0));
+
+        assert_eq!(
+            parse_rsync_progress("sending incremental file list", &regex, 100),
+            None
+        );
+        assert_eq!(parse_rsync_progress("", &regex, 100), None);
+    }
 }
@@ -0,0 +1,73 @@
+//! Expand `$NAME` and `${NAME}` references in a string, like a shell would.
+//! Unknown variables expand to an empty string and `$$` to a literal `$`.
+
+pub fn expand_variables(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+        let name: String = match chars.peek() {
+            Some('$') => {
+                chars.next();
+                expanded.push('$');
+                continue;
+            }
+            Some('{') => {
+                chars.next();
+                chars.by_ref().take_while(|c| *c != '}').collect()
+            }
+            Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                name
+            }
+            _ => {
+                expanded.push('$');
+                continue;
+            }
+        };
+        match lookup(&name) {
+            Some(value) => expanded.push_str(&value),
+            None => log::debug!("Variable {} is not set", name),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "GAME" => Some("my-game".to_string()),
+            "DIR" => Some("/games".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_expand_variables() {
+        assert_eq!(expand_variables("$DIR/$GAME", &lookup), "/games/my-game");
+        assert_eq!(
+            expand_variables("${DIR}/${GAME}.exe", &lookup),
+            "/games/my-game.exe"
+        );
+        assert_eq!(expand_variables("$GAME_x ${GAME}_x", &lookup), " my-game_x");
+        assert_eq!(expand_variables("[$UNKNOWN]", &lookup), "[]");
+        assert_eq!(
+            expand_variables("$$GAME costs 5$", &lookup),
+            "$GAME costs 5$"
+        );
+        assert_eq!(expand_variables("$1 $-", &lookup), "$1 $-");
+        // An unterminated reference takes the rest of the string as the name
+        assert_eq!(expand_variables("a${GAME", &lookup), "amy-game");
+        assert_eq!(expand_variables("no variables", &lookup), "no variables");
+    }
+}